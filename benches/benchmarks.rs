@@ -0,0 +1,88 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hashbrown::HashSet;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+use significance_clustering::clustering::{get_significant_core, Objective, Scorer};
+use significance_clustering::NodeId;
+
+fn bench_get_significant_core(c: &mut Criterion) {
+    let module = (0..10).collect::<HashSet<NodeId>>();
+
+    let modules = [
+        (0..10).collect::<HashSet<_>>(),
+        (0..10).collect::<HashSet<_>>(),
+        (1..11).collect::<HashSet<_>>(),
+        (0..10).collect::<HashSet<_>>(),
+        (0..10).collect::<HashSet<_>>(),
+    ];
+
+    c.bench_function("get_significant_core", |b| {
+        b.iter(|| {
+            get_significant_core(&module, &modules.iter().collect::<Vec<_>>(), 0.95, 123);
+        })
+    });
+}
+
+fn bench_score(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+
+    const NUM_NODES: u32 = 1_000;
+    const NUM_PARTITIONS: usize = 100;
+
+    let module = (0..NUM_NODES).collect::<HashSet<_>>();
+    let mut modules = Vec::with_capacity(NUM_PARTITIONS);
+
+    for _ in 0..NUM_PARTITIONS {
+        let mut module = module.clone();
+
+        let num_remove: u32 = rng.gen_range(0..(NUM_NODES / 10));
+
+        for _ in 0..num_remove {
+            let node = *module.iter().choose(&mut rng).unwrap();
+            module.remove(&node);
+        }
+
+        modules.push(module);
+    }
+
+    let num_partitions_to_exclude = ((1.0 - 0.95) * modules.len() as f32) as usize;
+    let penalty_weight = 10 * module.len() as i64;
+    let scorer = Scorer::new(penalty_weight, modules.len() - num_partitions_to_exclude);
+
+    c.bench_function("score", |b| {
+        b.iter(|| scorer.score(&module, &modules.iter().collect::<Vec<_>>()))
+    });
+}
+
+fn bench_collect(c: &mut Criterion) {
+    c.bench_function("collect", |b| {
+        b.iter(|| {
+            let path = black_box("1:2:3").split(':').collect::<Vec<_>>();
+            let level: u8 = path.len().try_into().unwrap();
+            let last: u32 = path.last().and_then(|s| s.parse().ok()).unwrap();
+            (level, last)
+        })
+    });
+}
+
+#[allow(clippy::double_ended_iterator_last)]
+fn bench_clone(c: &mut Criterion) {
+    c.bench_function("clone", |b| {
+        b.iter(|| {
+            let path = black_box("1:2:3").split(':');
+            let level: u8 = path.clone().count().try_into().unwrap();
+            let last: u32 = path.last().and_then(|s| s.parse().ok()).unwrap();
+            (level, last)
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_get_significant_core,
+    bench_score,
+    bench_collect,
+    bench_clone
+);
+criterion_main!(benches);
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Gamma, Poisson};
+
+use crate::io::Labels;
+use crate::{HashSet, Network, NetworkId, NodeId};
+
+/// A weighted network, read from a Pajek `.net` file or a `source target [weight]` link list.
+#[derive(Debug, Clone, Default)]
+pub struct LinkList {
+    pub directed: bool,
+    /// Vertices with their names, if read from a Pajek file.
+    pub vertices: Option<Vec<(NodeId, String)>>,
+    pub links: Vec<(NodeId, NodeId, f64)>,
+}
+
+impl LinkList {
+    /// All vertices and linked nodes.
+    pub fn nodes(&self) -> HashSet<NodeId> {
+        let mut nodes = self
+            .links
+            .iter()
+            .flat_map(|&(source, target, _)| [source, target])
+            .collect::<HashSet<_>>();
+
+        if let Some(vertices) = &self.vertices {
+            nodes.extend(vertices.iter().map(|&(node, _)| node));
+        }
+
+        nodes
+    }
+}
+
+/// Reads a Pajek file with `*Vertices` and `*Edges` or `*Arcs` sections,
+/// or else an undirected link list. Links without weight have weight 1.
+pub fn read_links(
+    contents: &str,
+    labels: &mut Labels,
+) -> Result<LinkList, Box<dyn std::error::Error>> {
+    let mut links = LinkList::default();
+    let mut in_vertices = false;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with('%') {
+            continue;
+        }
+
+        if line.starts_with('*') {
+            let heading = line.split_whitespace().next().unwrap().to_lowercase();
+
+            match heading.as_str() {
+                "*vertices" => {
+                    links.vertices.get_or_insert_with(Vec::new);
+                    in_vertices = true;
+                }
+                "*edges" | "*arcs" => {
+                    links.directed = heading == "*arcs";
+                    in_vertices = false;
+                }
+                _ => {
+                    return Err(format!(
+                        "Line {}: unsupported section {}",
+                        line_number + 1,
+                        heading
+                    )
+                    .into())
+                }
+            }
+
+            continue;
+        }
+
+        if in_vertices {
+            let (node, name) = line.split_once(char::is_whitespace).unwrap_or((line, line));
+            let node = labels.intern(node);
+            let name = match name.trim().strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next().unwrap(),
+                None => name.split_whitespace().next().unwrap_or(name),
+            };
+            links
+                .vertices
+                .as_mut()
+                .unwrap()
+                .push((node, name.to_owned()));
+            continue;
+        }
+
+        let mut cols = line.split_whitespace();
+
+        let (source, target) = match (cols.next(), cols.next()) {
+            (Some(source), Some(target)) => (labels.intern(source), labels.intern(target)),
+            _ => {
+                return Err(format!("Line {}: expected source and target", line_number + 1).into())
+            }
+        };
+
+        let weight = match cols.next().map(str::parse::<f64>) {
+            None => 1.0,
+            Some(Ok(weight)) if weight >= 0.0 && weight.is_finite() => weight,
+            Some(_) => {
+                return Err(format!(
+                    "Line {}: weight must be a finite non-negative number",
+                    line_number + 1
+                )
+                .into())
+            }
+        };
+
+        links.links.push((source, target, weight));
+    }
+
+    Ok(links)
+}
+
+/// Writes `links` in the format it was read from. Directed links always get
+/// an `*Arcs` heading, since a plain link list is read as undirected.
+pub fn write_link_list(
+    links: &LinkList,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> std::io::Result<()> {
+    if let Some(vertices) = &links.vertices {
+        writeln!(f, "*Vertices {}", vertices.len())?;
+
+        for (node, name) in vertices.iter() {
+            writeln!(f, "{} \"{}\"", labels.label(*node), name)?;
+        }
+    }
+
+    if links.vertices.is_some() || links.directed {
+        writeln!(f, "{}", if links.directed { "*Arcs" } else { "*Edges" })?;
+    }
+
+    for &(source, target, weight) in links.links.iter() {
+        writeln!(
+            f,
+            "{} {} {}",
+            labels.label(source),
+            labels.label(target),
+            weight
+        )?;
+    }
+
+    Ok(())
+}
+
+/// How to draw the link weights of a bootstrap replicate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resampling {
+    /// Poisson distributed with the observed weight as mean, as if each
+    /// weight counts independent observations such as citations.
+    Poisson,
+    /// Gamma distributed with the observed weight as mean and coefficient of variation `cv`.
+    Gamma { cv: f64 },
+}
+
+impl Resampling {
+    fn sample(&self, weight: f64, rng: &mut StdRng) -> f64 {
+        if weight <= 0.0 {
+            return 0.0;
+        }
+
+        match *self {
+            Resampling::Poisson => Poisson::new(weight).unwrap().sample(rng),
+            Resampling::Gamma { cv } => Gamma::new(1.0 / (cv * cv), weight * cv * cv)
+                .unwrap()
+                .sample(rng),
+        }
+    }
+}
+
+/// Draws a bootstrap replicate of `links`, dropping links whose weight becomes zero.
+pub fn resample(links: &LinkList, resampling: Resampling, seed: u64) -> LinkList {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    LinkList {
+        directed: links.directed,
+        vertices: links.vertices.clone(),
+        links: links
+            .links
+            .iter()
+            .filter_map(|&(source, target, weight)| {
+                let weight = resampling.sample(weight, &mut rng);
+                (weight > 0.0).then_some((source, target, weight))
+            })
+            .collect(),
+    }
+}
+
+/// Partitions a network into a possibly multi-level partition, such as with
+/// Infomap, so that bootstrap replicates can be clustered directly.
+pub trait Partitioner: Send + Sync {
+    fn partition(&self, links: &LinkList, seed: u64) -> Network;
+}
+
+/// Partitions `links` as network 0 and `replicates` bootstrap replicates of
+/// it as the following networks, ready for clustering.
+///
+/// Replicate `i` is drawn and partitioned with seed `seed + i`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn partition_replicates(
+    links: &LinkList,
+    resampling: Resampling,
+    replicates: usize,
+    seed: u64,
+    partitioner: &dyn Partitioner,
+) -> BTreeMap<NetworkId, Network> {
+    use rayon::prelude::*;
+
+    (0..=replicates)
+        .into_par_iter()
+        .map(|i| {
+            let seed = seed.wrapping_add(i as u64);
+            let network = match i {
+                0 => partitioner.partition(links, seed),
+                _ => partitioner.partition(&resample(links, resampling, seed), seed),
+            };
+            (i, network)
+        })
+        .collect()
+}
+
+/// Reads the network in a possibly compressed file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_links_file(file_name: &str) -> Result<(LinkList, Labels), Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let mut contents = String::new();
+    crate::io::open(file_name)?.read_to_string(&mut contents)?;
+
+    let mut labels = Labels::new();
+    let links =
+        read_links(&contents, &mut labels).map_err(|err| format!("{}: {}", file_name, err))?;
+
+    Ok((links, labels))
+}
+
+/// Reads the network in `in_file` and writes `replicates` bootstrap replicates
+/// of it to `dir`, with the same seeds as [`partition_replicates`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_replicates(
+    in_file: &str,
+    dir: &str,
+    resampling: Resampling,
+    replicates: usize,
+    seed: u64,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let (links, labels) = read_links_file(in_file)?;
+
+    std::fs::create_dir_all(dir)?;
+
+    let extension = if links.vertices.is_some() {
+        "net"
+    } else {
+        "txt"
+    };
+    let width = replicates.to_string().len();
+    let mut files = Vec::with_capacity(replicates);
+
+    for i in 1..=replicates {
+        let replicate = resample(&links, resampling, seed.wrapping_add(i as u64));
+
+        let file = std::path::Path::new(dir).join(format!(
+            "replicate_{:0width$}.{}",
+            i,
+            extension,
+            width = width
+        ));
+        let file = file.to_string_lossy().into_owned();
+
+        write_link_list(&replicate, &labels, &mut crate::io::create(&file)?)?;
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_links() {
+        let mut labels = Labels::new();
+        let input = "*Vertices 3\n1 \"a b\"\n2 \"c\"\n3 \"d\"\n*Arcs\n1 2 3\n2 3 0.5\n";
+        let with_weights = "*Vertices 3\n1 \"a b\" 1.0\n2 c\n3 \"d\"\n*Arcs\n1 2 3\n2 3 0.5\n";
+        let links = read_links(with_weights, &mut labels).unwrap();
+
+        assert!(links.directed);
+        assert_eq!(links.vertices.as_ref().unwrap()[0].1, "a b");
+        assert_eq!(links.links.len(), 2);
+        assert_eq!(links.links[1].2, 0.5);
+
+        let mut output = Vec::new();
+        write_link_list(&links, &labels, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+
+        let links = read_links("# source target\n1 2\n2 4 2\n", &mut labels).unwrap();
+        assert!(!links.directed);
+        assert!(links.vertices.is_none());
+        assert_eq!(links.links[0].2, 1.0);
+        assert_eq!(links.nodes().len(), 3);
+
+        // Directed links keep their heading without vertices
+        let input = "*Arcs\n1 2 1\n";
+        let links = read_links(input, &mut labels).unwrap();
+        assert!(links.directed);
+
+        let mut output = Vec::new();
+        write_link_list(&links, &labels, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+
+        assert!(read_links("1 2 -1\n", &mut labels).is_err());
+        assert!(read_links("1 2 inf\n", &mut labels).is_err());
+        assert!(read_links("1 2 NaN\n", &mut labels).is_err());
+        assert!(read_links("*Edgeslist\n1 2 3\n", &mut labels).is_err());
+    }
+
+    #[test]
+    fn test_resample() {
+        let links = LinkList {
+            links: (0..100).map(|i| (i, i + 1, 10.0)).collect(),
+            ..Default::default()
+        };
+
+        for resampling in [Resampling::Poisson, Resampling::Gamma { cv: 0.5 }] {
+            let replicate = resample(&links, resampling, 1);
+            assert_eq!(
+                replicate.links,
+                resample(&links, resampling, 1).links,
+                "same seed, same replicate"
+            );
+            assert_ne!(replicate.links, resample(&links, resampling, 2).links);
+
+            let total = replicate.links.iter().map(|&(_, _, w)| w).sum::<f64>();
+            assert!((800.0..1200.0).contains(&total));
+        }
+
+        let zero = LinkList {
+            links: vec![(0, 1, 0.0)],
+            ..Default::default()
+        };
+        assert!(resample(&zero, Resampling::Poisson, 1).links.is_empty());
+    }
+
+    /// Puts the source of each link in module 1, and the other nodes in module 2.
+    struct SourcePartitioner;
+
+    impl Partitioner for SourcePartitioner {
+        fn partition(&self, links: &LinkList, _seed: u64) -> Network {
+            let sources = links
+                .links
+                .iter()
+                .map(|&(source, _, _)| source)
+                .collect::<HashSet<_>>();
+
+            let mut network = Network::new();
+            for node in links.nodes() {
+                let module_id = if sources.contains(&node) { "1" } else { "2" };
+                network.add_node(module_id, node);
+            }
+            network
+        }
+    }
+
+    #[test]
+    fn test_partition_replicates() {
+        let links = LinkList {
+            links: (0..20).map(|i| (i, i + 1, 1.0)).collect(),
+            ..Default::default()
+        };
+
+        let networks = partition_replicates(&links, Resampling::Poisson, 3, 10, &SourcePartitioner);
+        assert_eq!(
+            networks.keys().copied().collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+
+        // The reference partition is of the network itself
+        assert_eq!(networks[&0].modules["1"].nodes.len(), 20);
+
+        for (&i, network) in networks.iter().skip(1) {
+            let replicate = resample(&links, Resampling::Poisson, 10 + i as u64);
+            let expected = SourcePartitioner.partition(&replicate, 10 + i as u64);
+            assert_eq!(network.nodes, expected.nodes);
+            assert_eq!(network.modules["1"].nodes, expected.modules["1"].nodes);
+        }
+
+        // Poisson resampling with mean 1 drops some links
+        assert!(networks
+            .values()
+            .skip(1)
+            .any(|network| network.modules["1"].nodes.len() < 20));
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use itertools::Itertools;
+
+use crate::io::{cmp_module_ids, Labels};
+use crate::{Network, NetworkId, NodeId};
+
+/// Magic bytes at the start of every cache file.
+pub const MAGIC: &[u8; 4] = b"MSCC";
+
+/// Bumped whenever the layout changes.
+pub const VERSION: u32 = 1;
+
+/// Writes the parsed partitions in a compact binary format.
+///
+/// All integers are little endian `u32`. After the magic bytes and the
+/// version follow the node labels, and then for each network its id and
+/// modules, each module as its id followed by its node ids. Modules and nodes
+/// are sorted so that the same partitions always give the same bytes. Links
+/// between modules, the ranks, flows and names of tree nodes, and which nodes
+/// were read more than once are not cached.
+pub fn write_cache(
+    networks: &BTreeMap<NetworkId, Network>,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> std::io::Result<()> {
+    fn write_u32(f: &mut impl Write, value: usize) -> std::io::Result<()> {
+        f.write_all(&(value as u32).to_le_bytes())
+    }
+
+    fn write_str(f: &mut impl Write, value: &str) -> std::io::Result<()> {
+        write_u32(f, value.len())?;
+        f.write_all(value.as_bytes())
+    }
+
+    f.write_all(MAGIC)?;
+    write_u32(f, VERSION as usize)?;
+
+    write_u32(f, labels.len())?;
+    for id in 0..labels.len() {
+        write_str(f, labels.label(id as NodeId))?;
+    }
+
+    write_u32(f, networks.len())?;
+    for (&network_id, network) in networks.iter() {
+        write_u32(f, network_id)?;
+        write_u32(f, network.nodes.len())?;
+        for &node in network.nodes.iter().sorted() {
+            write_u32(f, node as usize)?;
+        }
+
+        write_u32(f, network.modules.len())?;
+        for (module_id, module) in network
+            .modules
+            .iter()
+            .sorted_by(|(a, _), (b, _)| cmp_module_ids(a, b))
+        {
+            write_str(f, module_id)?;
+            write_u32(f, module.nodes.len())?;
+            for &node in module.nodes.iter().sorted() {
+                write_u32(f, node as usize)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.bytes.len() < len {
+            return Err("Truncated cache file");
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, &'static str> {
+        self.u32().map(|value| value as usize)
+    }
+
+    fn str(&mut self) -> Result<&'a str, &'static str> {
+        let len = self.usize()?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| "Invalid label in cache file")
+    }
+}
+
+pub fn is_cache(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Reads partitions written by [`write_cache`].
+pub fn read_cache(
+    bytes: &[u8],
+) -> Result<(BTreeMap<NetworkId, Network>, Labels), Box<dyn std::error::Error>> {
+    if !is_cache(bytes) {
+        return Err("Not a cache file".into());
+    }
+
+    let mut cursor = Cursor {
+        bytes: &bytes[MAGIC.len()..],
+    };
+
+    let version = cursor.u32()?;
+    if version != VERSION {
+        return Err(format!(
+            "Unsupported cache version {}, expected {}",
+            version, VERSION
+        )
+        .into());
+    }
+
+    let mut labels = Labels::new();
+    for _ in 0..cursor.usize()? {
+        labels.intern(cursor.str()?);
+    }
+
+    let num_labels = labels.len() as u32;
+    let node = |cursor: &mut Cursor| match cursor.u32()? {
+        node if node < num_labels => Ok(node),
+        _ => Err("Invalid node in cache file"),
+    };
+
+    let mut networks = BTreeMap::new();
+
+    for _ in 0..cursor.usize()? {
+        let network_id = cursor.usize()?;
+        let mut network = Network::new();
+
+        for _ in 0..cursor.usize()? {
+            network.nodes.insert(node(&mut cursor)?);
+        }
+
+        for _ in 0..cursor.usize()? {
+            let module_id = cursor.str()?;
+
+            for _ in 0..cursor.usize()? {
+                network.add_node(module_id, node(&mut cursor)?);
+            }
+        }
+
+        networks.insert(network_id, network);
+    }
+
+    Ok((networks, labels))
+}
+
+/// Checks if `file_name` starts with the cache magic bytes.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn is_cache_file(file_name: &str) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0; 4];
+
+    std::fs::File::open(file_name)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| is_cache(&magic))
+        .unwrap_or(false)
+}
+
+/// Reads the partitions in the cache file `file_name`.
+///
+/// The file is read into memory rather than memory mapped. [`read_cache`]
+/// builds owned networks from the bytes either way, so a mapping would not
+/// save the copy, and it would need an `unsafe` block that relies on the file
+/// not changing while it is read.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(
+    file_name: &str,
+) -> Result<(BTreeMap<NetworkId, Network>, Labels), Box<dyn std::error::Error>> {
+    read_cache(&std::fs::read(file_name)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let input = "1 1:1 a\n2 1:2 a\nQ3 2 -\n";
+        let (networks, labels) = crate::io::read_input(input).unwrap();
+
+        let mut bytes = Vec::new();
+        write_cache(&networks, &labels, &mut bytes).unwrap();
+        assert!(is_cache(&bytes));
+
+        let (cached, cached_labels) = read_cache(&bytes).unwrap();
+        assert_eq!(cached_labels.len(), labels.len());
+        assert_eq!(cached_labels.get("Q3"), labels.get("Q3"));
+        assert_eq!(cached.len(), networks.len());
+
+        for (network_id, network) in networks.iter() {
+            let other = &cached[network_id];
+            assert_eq!(other.nodes, network.nodes);
+            assert_eq!(other.modules.len(), network.modules.len());
+
+            for (module_id, module) in network.modules.iter() {
+                assert_eq!(other.modules[module_id].nodes, module.nodes);
+                assert_eq!(other.modules[module_id].level, module.level);
+            }
+        }
+
+        // The same partitions give the same bytes
+        let mut again = Vec::new();
+        write_cache(&cached, &cached_labels, &mut again).unwrap();
+        assert_eq!(again, bytes);
+
+        assert!(read_cache(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -6,13 +6,40 @@ use rand::rngs::StdRng;
 use rand::seq::IteratorRandom;
 use rand::{Rng, SeedableRng};
 
-use crate::{HashMap, HashSet, NodeId};
+use crate::optimizer::{Annealing, CoreOptimizer, Params, Restarts};
+use crate::similarity::jaccard_distance;
+use crate::{HashMap, HashSet, Module, NodeId};
 
 pub fn get_significant_core(
     module: &HashSet<NodeId>,
     modules: &[&HashSet<NodeId>],
     conf: f32,
     seed: u64,
+) -> HashSet<NodeId> {
+    let scorer = Scorer::for_module(module, modules, conf);
+    anneal(module, modules, &scorer, seed, None)
+}
+
+/// Like [`get_significant_core`], but starts the annealing from `initial`
+/// instead of a random core, e.g. from [`get_significant_core_greedy`].
+pub fn get_significant_core_warm_start(
+    module: &HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    conf: f32,
+    seed: u64,
+    initial: &HashSet<NodeId>,
+) -> HashSet<NodeId> {
+    let scorer = Scorer::for_module(module, modules, conf);
+    anneal(module, modules, &scorer, seed, Some(initial))
+}
+
+/// Simulated annealing of the core, maximizing `objective`.
+pub fn anneal(
+    module: &HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    objective: &dyn Objective,
+    seed: u64,
+    initial: Option<&HashSet<NodeId>>,
 ) -> HashSet<NodeId> {
     let (mut core, candidates) = {
         // Count the number of modules that each node is in
@@ -46,18 +73,27 @@ pub fn get_significant_core(
 
     let mut rng = StdRng::seed_from_u64(seed);
 
-    // Randomize start
-    for &node in candidates.iter() {
-        if rng.gen::<bool>() {
-            core.insert(node);
+    match initial {
+        Some(initial) => {
+            for &node in candidates.iter() {
+                if initial.contains(&node) {
+                    core.insert(node);
+                }
+            }
+        }
+        None => {
+            // Randomize start
+            for &node in candidates.iter() {
+                if rng.gen::<bool>() {
+                    core.insert(node);
+                }
+            }
         }
     }
 
-    let penalty_weight = 10 * module.len() as i64;
-
-    let scorer = Scorer::new(penalty_weight, get_num_to_exclude(modules.len(), conf));
+    let penalty_weight = objective.penalty_weight();
 
-    let (mut score, mut penalty) = scorer.score(&core, modules);
+    let (mut score, mut penalty) = objective.score(&core, modules);
 
     const MAX_OUTER_LOOPS: usize = 1000;
     const MAX_INNER_LOOPS: usize = 1000;
@@ -65,6 +101,7 @@ pub fn get_significant_core(
 
     let mut best_score = None;
 
+    #[allow(clippy::type_complexity)]
     fn flip(
         set: &mut HashSet<NodeId>,
         node_id: NodeId,
@@ -95,7 +132,7 @@ pub fn get_significant_core(
                 // Remove or add the node
                 let revert = flip(&mut core, node_id, remove);
 
-                let (new_score, new_penalty) = scorer.score(&core, modules);
+                let (new_score, new_penalty) = objective.score(&core, modules);
 
                 let delta_s = {
                     let s = score - penalty_weight * penalty;
@@ -134,6 +171,230 @@ pub fn get_significant_core(
     core
 }
 
+/// Deterministic greedy alternative to [`get_significant_core`].
+///
+/// Greedily picks the matched modules to exclude, one at a time, preferring
+/// the module whose exclusion brings the most nodes closer to being present
+/// in all remaining modules. The core is the set of nodes present in all
+/// modules that are left.
+pub fn get_significant_core_greedy(
+    module: &HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    conf: f32,
+) -> HashSet<NodeId> {
+    let num_to_exclude = get_num_to_exclude(modules.len(), conf);
+
+    // The modules that each node is missing from, for nodes that can
+    // still end up in the core
+    let mut missing = module
+        .iter()
+        .filter_map(|&node| {
+            let missing_from = modules
+                .iter()
+                .enumerate()
+                .filter_map(|(i, m)| (!m.contains(&node)).then_some(i))
+                .collect::<Vec<_>>();
+
+            let in_any = missing_from.len() < modules.len() || modules.is_empty();
+            let possible = missing_from.len() <= num_to_exclude;
+
+            (in_any && possible).then_some((node, missing_from))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut excluded = HashSet::new();
+
+    while excluded.len() < num_to_exclude {
+        let mut gains = vec![0.0; modules.len()];
+
+        for missing_from in missing.values() {
+            let weight = 1.0 / missing_from.len() as f64;
+            for &i in missing_from.iter() {
+                gains[i] += weight;
+            }
+        }
+
+        // Break ties by the lowest module index to stay deterministic
+        let best = gains
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !excluded.contains(i))
+            .fold(None, |best: Option<(usize, f64)>, (i, &gain)| match best {
+                Some((_, best_gain)) if best_gain >= gain => best,
+                _ => Some((i, gain)),
+            });
+
+        let best = match best {
+            Some((best, gain)) if gain > 0.0 => best,
+            _ => break,
+        };
+
+        excluded.insert(best);
+
+        let budget = num_to_exclude - excluded.len();
+
+        for missing_from in missing.values_mut() {
+            missing_from.retain(|&i| i != best);
+        }
+
+        missing.retain(|_, missing_from| missing_from.len() <= budget);
+    }
+
+    missing
+        .into_iter()
+        .filter_map(|(node, missing_from)| missing_from.is_empty().then_some(node))
+        .collect()
+}
+
+/// Exact alternative to [`get_significant_core`] for the default [`Scorer`].
+///
+/// Tries every combination of matched modules to exclude and keeps the
+/// largest set of nodes present in all remaining modules. The number of
+/// combinations grows quickly with the number of modules to exclude, see
+/// [`num_exact_combinations`] and [`MAX_EXACT_COMBINATIONS`].
+pub fn get_significant_core_exact(
+    module: &HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    conf: f32,
+) -> HashSet<NodeId> {
+    let num_to_keep = modules.len() - get_num_to_exclude(modules.len(), conf).min(modules.len());
+
+    (0..modules.len())
+        .combinations(num_to_keep)
+        .map(|kept| {
+            module
+                .iter()
+                .copied()
+                .filter(|node| kept.iter().all(|&i| modules[i].contains(node)))
+                .collect::<HashSet<_>>()
+        })
+        .fold(None, |best: Option<HashSet<_>>, core| match best {
+            Some(best) if best.len() >= core.len() => Some(best),
+            _ => Some(core),
+        })
+        .unwrap_or_else(|| module.clone())
+}
+
+/// Most combinations [`get_significant_core_exact`] is allowed to try for one module.
+pub const MAX_EXACT_COMBINATIONS: u64 = 10_000_000;
+
+/// Number of combinations [`get_significant_core_exact`] tries for a module
+/// with `num_modules` matched modules, saturating at `u64::MAX`.
+pub fn num_exact_combinations(num_modules: usize, conf: f32) -> u64 {
+    let num_to_exclude = get_num_to_exclude(num_modules, conf).min(num_modules);
+    let k = num_to_exclude.min(num_modules - num_to_exclude) as u64;
+
+    // C(n, k) = C(n, k - 1) * (n - k + 1) / k, which is exact at every step
+    (1..=k).fold(1u64, |combinations, i| {
+        combinations
+            .checked_mul(num_modules as u64 - i + 1)
+            .map_or(u64::MAX, |product| product / i)
+    })
+}
+
+pub fn get_significant_core_consensus(
+    module: &HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    conf: f32,
+    seed: u64,
+    num_restarts: usize,
+) -> (HashSet<NodeId>, HashMap<NodeId, f32>) {
+    let result = Restarts::new(Annealing::default(), num_restarts).optimize(
+        module,
+        modules,
+        &Params::new(conf, seed),
+    );
+
+    (result.core, result.diagnostics.frequencies)
+}
+
+/// The fraction of matched modules that contain each node of the module.
+pub fn get_support(module: &HashSet<NodeId>, modules: &[&HashSet<NodeId>]) -> HashMap<NodeId, f32> {
+    module
+        .iter()
+        .map(|&node| {
+            let count = modules.iter().filter(|m| m.contains(&node)).count();
+            (node, count as f32 / max(modules.len(), 1) as f32)
+        })
+        .collect()
+}
+
+/// Removes nodes from the core of each module that are not in the core of
+/// its parent module, so that insignificance propagates down the hierarchy.
+pub fn propagate_insignificance(
+    modules: &HashMap<String, Module>,
+    cores: &mut HashMap<String, HashSet<NodeId>>,
+) {
+    let module_ids = cores
+        .keys()
+        .cloned()
+        .sorted_by_key(|module_id| modules[module_id].level)
+        .collect::<Vec<_>>();
+
+    for module_id in module_ids {
+        let mut core = cores.remove(&module_id).unwrap();
+
+        if let Some(parent_core) = modules[&module_id]
+            .parent_id()
+            .and_then(|parent_id| cores.get(parent_id))
+        {
+            core.retain(|node| parent_core.contains(node));
+        }
+
+        cores.insert(module_id, core);
+    }
+}
+
+/// Test of whether a module as a whole is significant, beyond its core.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModuleTest {
+    /// Every module is significant.
+    None,
+    /// The core holds at least this fraction of the module.
+    CoreFraction(f32),
+    /// The matched module has at least this Jaccard index with the module in
+    /// all but the `(1 - conf)` fraction of bootstrap partitions.
+    Jaccard(f32),
+}
+
+impl ModuleTest {
+    /// Tests the module against its matched `modules`. The Jaccard index with
+    /// each matched module only counts the module nodes that are present in
+    /// its partition, that is, not in the corresponding `absent` set.
+    pub fn is_significant(
+        &self,
+        module: &HashSet<NodeId>,
+        core: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        absent: &[HashSet<NodeId>],
+        conf: f32,
+    ) -> bool {
+        match *self {
+            ModuleTest::None => true,
+            ModuleTest::CoreFraction(fraction) => {
+                core.len() as f32 >= fraction * module.len() as f32
+            }
+            ModuleTest::Jaccard(threshold) => {
+                let mismatches = modules
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, other)| {
+                        let distance = match absent.get(*i) {
+                            Some(absent) if !absent.is_empty() => {
+                                let present = module.difference(absent).copied().collect();
+                                jaccard_distance(&present, other)
+                            }
+                            _ => jaccard_distance(module, other),
+                        };
+                        1.0 - distance < threshold
+                    })
+                    .count();
+                mismatches <= get_num_to_exclude(modules.len(), conf)
+            }
+        }
+    }
+}
+
 trait IntersectionDifference {
     fn intersection_difference_count(&self, other: &Self) -> (usize, usize);
 }
@@ -154,26 +415,127 @@ where
     }
 }
 
-struct Scorer {
+/// Objective maximized by the annealing based core optimizers. The greedy
+/// and exact optimizers only support the default [`Scorer`].
+pub trait Objective {
+    /// Returns the score of `core` against the matched `modules`, and the
+    /// penalty for core nodes missing from the modules that count.
+    fn score(&self, core: &HashSet<NodeId>, modules: &[&HashSet<NodeId>]) -> (i64, i64);
+
+    /// How much one unit of penalty weighs against one unit of score.
+    fn penalty_weight(&self) -> i64;
+}
+
+/// Creates the objective for a module given its matched modules and the parameters.
+pub type ObjectiveFn = fn(&HashSet<NodeId>, &[&HashSet<NodeId>], &Params) -> Box<dyn Objective>;
+
+/// How core nodes that are absent from a bootstrap partition count in that partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Missing {
+    /// Absent nodes count as missing from the matched module.
+    Exclude,
+    /// Absent nodes count neither for nor against the core.
+    Ignore,
+    /// Like `Ignore`, but the score and penalty are scaled up to the full core size.
+    Renormalize,
+}
+
+/// The default objective: the number of core nodes in each matched module,
+/// summed over all but the `(1 - conf)` fraction of worst matching modules.
+pub struct Scorer {
     penalty_weight: i64,
     num_partitions_to_exclude: usize,
+    missing: Missing,
+    absent: Vec<HashSet<NodeId>>,
 }
 
 impl Scorer {
-    fn new(penalty_weight: i64, num_partitions_to_exclude: usize) -> Self {
+    pub fn new(penalty_weight: i64, num_partitions_to_exclude: usize) -> Self {
         Self {
             penalty_weight,
             num_partitions_to_exclude,
+            missing: Missing::Exclude,
+            absent: Vec::new(),
         }
     }
 
+    /// Sets the nodes absent from each matched module's partition, and how they count.
+    pub fn with_absent(mut self, missing: Missing, absent: Vec<HashSet<NodeId>>) -> Self {
+        self.missing = missing;
+        self.absent = absent;
+        self
+    }
+
+    pub fn for_module(module: &HashSet<NodeId>, modules: &[&HashSet<NodeId>], conf: f32) -> Self {
+        Self::new(
+            10 * module.len() as i64,
+            get_num_to_exclude(modules.len(), conf),
+        )
+    }
+
+    pub fn boxed(
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> Box<dyn Objective> {
+        let scorer = Self::for_module(module, modules, params.conf);
+
+        if params.missing == Missing::Exclude || params.absent.is_empty() {
+            return Box::new(scorer);
+        }
+
+        let absent = params
+            .absent
+            .iter()
+            .map(|absent| absent.intersection(module).copied().collect())
+            .collect();
+
+        Box::new(scorer.with_absent(params.missing, absent))
+    }
+
+    fn count(&self, core: &HashSet<NodeId>, module: &HashSet<NodeId>, i: usize) -> (i64, i64) {
+        let absent = match self.absent.get(i) {
+            Some(absent) if self.missing != Missing::Exclude && !absent.is_empty() => absent,
+            _ => {
+                let (score, penalty) = core.intersection_difference_count(module);
+                return (score as i64, penalty as i64);
+            }
+        };
+
+        let (score, penalty, present) = core.iter().filter(|node| !absent.contains(node)).fold(
+            (0, 0, 0),
+            |(score, penalty, present), node| {
+                if module.contains(node) {
+                    (score + 1, penalty, present + 1)
+                } else {
+                    (score, penalty + 1, present + 1)
+                }
+            },
+        );
+
+        match self.missing {
+            Missing::Renormalize if present > 0 => {
+                let total = core.len() as i64;
+                let scale = |count: i64| (count * total + present / 2) / present;
+                (scale(score), scale(penalty))
+            }
+            _ => (score, penalty),
+        }
+    }
+}
+
+impl Objective for Scorer {
+    fn penalty_weight(&self) -> i64 {
+        self.penalty_weight
+    }
+
     fn score(&self, module: &HashSet<NodeId>, modules: &[&HashSet<NodeId>]) -> (i64, i64) {
         // Sum the best scores and penalties
         modules
             .iter()
-            .map(|module2| {
-                let (score, penalty) = module.intersection_difference_count(module2);
-                let (score, penalty) = (score as i64, penalty as i64);
+            .enumerate()
+            .map(|(i, module2)| {
+                let (score, penalty) = self.count(module, module2, i);
                 (score - self.penalty_weight * penalty, score, penalty)
             })
             .sorted_by_key(|(module_score, ..)| *module_score)
@@ -184,16 +546,12 @@ impl Scorer {
     }
 }
 
-fn get_num_to_exclude(num_partitions: usize, conf: f32) -> usize {
+pub fn get_num_to_exclude(num_partitions: usize, conf: f32) -> usize {
     ((1.0 - conf) * num_partitions as f32 + 0.5) as usize
 }
 
 #[cfg(test)]
 mod tests {
-    extern crate test;
-
-    use test::Bencher;
-
     use super::*;
 
     #[test]
@@ -256,13 +614,147 @@ mod tests {
         );
     }
 
-    #[bench]
-    fn bench_get_significant_core(b: &mut Bencher) {
+    #[test]
+    fn test_get_significant_core_consensus() {
         let (module, modules) = setup();
 
-        b.iter(|| {
-            get_significant_core(&module, &modules.iter().collect::<Vec<_>>(), 0.95, 123);
-        });
+        let (core, frequencies) = get_significant_core_consensus(
+            &module,
+            &modules.iter().collect::<Vec<_>>(),
+            0.95,
+            123,
+            5,
+        );
+
+        assert_eq!(core, (1..10).collect::<HashSet<_>>());
+        assert_eq!(frequencies.len(), module.len());
+        assert_eq!(frequencies[&0], 0.0);
+
+        for node in 1..10 {
+            assert_eq!(frequencies[&node], 1.0);
+        }
+    }
+
+    #[test]
+    fn test_get_significant_core_greedy() {
+        let (module, modules) = setup();
+        let modules = modules.iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            get_significant_core_greedy(&module, &modules, 0.95),
+            (1..10).collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            get_significant_core_greedy(&module, &modules, 0.8),
+            (0..10).collect::<HashSet<_>>()
+        );
+
+        let greedy = get_significant_core_greedy(&module, &modules, 0.95);
+        assert_eq!(
+            get_significant_core_warm_start(&module, &modules, 0.95, 123, &greedy),
+            (1..10).collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_get_significant_core_exact() {
+        let (module, modules) = setup();
+        let modules = modules.iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            get_significant_core_exact(&module, &modules, 0.95),
+            (1..10).collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            get_significant_core_exact(&module, &modules, 0.8),
+            (0..10).collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_propagate_insignificance() {
+        let mut modules = HashMap::new();
+        for (module_id, nodes) in [("1", 0..10), ("1:1", 0..5), ("1:1:1", 0..3)] {
+            let mut module = Module::new(module_id);
+            module.nodes.extend(nodes);
+            modules.insert(module_id.to_string(), module);
+        }
+
+        let mut cores = HashMap::new();
+        cores.insert("1".to_string(), (1..10).collect::<HashSet<_>>());
+        cores.insert("1:1".to_string(), (0..4).collect::<HashSet<_>>());
+        cores.insert("1:1:1".to_string(), (0..3).collect::<HashSet<_>>());
+
+        propagate_insignificance(&modules, &mut cores);
+
+        assert_eq!(cores["1"], (1..10).collect::<HashSet<_>>());
+        assert_eq!(cores["1:1"], (1..4).collect::<HashSet<_>>());
+        assert_eq!(cores["1:1:1"], (1..3).collect::<HashSet<_>>());
+    }
+
+    #[test]
+    fn test_module_test() {
+        let (module, modules) = setup();
+        let modules = modules.iter().collect::<Vec<_>>();
+        let core = module.iter().take(module.len() / 2).copied().collect();
+
+        assert!(ModuleTest::None.is_significant(&module, &HashSet::new(), &modules, &[], 0.95));
+        assert!(ModuleTest::CoreFraction(0.5).is_significant(&module, &core, &modules, &[], 0.95));
+        assert!(!ModuleTest::CoreFraction(0.6).is_significant(&module, &core, &modules, &[], 0.95));
+
+        let jaccards = modules
+            .iter()
+            .map(|other| 1.0 - jaccard_distance(&module, other))
+            .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+            .collect::<Vec<_>>();
+        let lowest = jaccards[0];
+        assert!(ModuleTest::Jaccard(lowest).is_significant(&module, &core, &modules, &[], 1.0));
+        assert!(!ModuleTest::Jaccard(lowest + 0.01).is_significant(
+            &module,
+            &core,
+            &modules,
+            &[],
+            1.0
+        ));
+
+        // Only the third module is below the threshold, which is exactly the
+        // number of partitions to exclude
+        assert_eq!(get_num_to_exclude(modules.len(), 0.8), 1);
+        assert!(ModuleTest::Jaccard(0.9).is_significant(&module, &core, &modules, &[], 0.8));
+
+        let mismatch = (2..12).collect::<HashSet<_>>();
+        let mut two_mismatches = modules.clone();
+        two_mismatches[1] = &mismatch;
+        assert!(!ModuleTest::Jaccard(0.9).is_significant(
+            &module,
+            &core,
+            &two_mismatches,
+            &[],
+            0.8
+        ));
+
+        // Node 0 is absent from the partition of the third module, which then
+        // matches the present nodes with Jaccard index 0.9
+        let absent = vec![
+            HashSet::new(),
+            HashSet::new(),
+            [0].into_iter().collect(),
+            HashSet::new(),
+            HashSet::new(),
+        ];
+        assert!(!ModuleTest::Jaccard(0.9).is_significant(&module, &core, &modules, &[], 1.0));
+        assert!(ModuleTest::Jaccard(0.9).is_significant(&module, &core, &modules, &absent, 1.0));
+    }
+
+    #[test]
+    fn test_num_exact_combinations() {
+        assert_eq!(num_exact_combinations(5, 1.0), 1);
+        assert_eq!(num_exact_combinations(10, 0.8), 45);
+        assert_eq!(num_exact_combinations(100, 0.9), 17_310_309_456_440);
+        assert!(num_exact_combinations(100, 0.9) > MAX_EXACT_COMBINATIONS);
+        assert_eq!(num_exact_combinations(0, 0.9), 1);
     }
 
     #[test]
@@ -284,33 +776,33 @@ mod tests {
         assert_eq!(penalty, 1);
     }
 
-    #[bench]
-    fn bench_score(b: &mut Bencher) {
-        let mut rng = rand::thread_rng();
-
-        const NUM_NODES: u32 = 1_000;
-        const NUM_PARTITIONS: usize = 100;
-
-        let module = (0..NUM_NODES).collect::<HashSet<_>>();
-        let mut modules = Vec::with_capacity(NUM_PARTITIONS);
-
-        for _ in 0..NUM_PARTITIONS {
-            let mut module = module.clone();
-
-            let num_remove: u32 = rng.gen_range(0..((NUM_NODES / 10) as u32));
-
-            for _ in 0..num_remove {
-                let node = *module.iter().choose(&mut rng).unwrap();
-                module.remove(&node);
-            }
+    #[test]
+    fn test_calc_score_missing() {
+        let (module, modules) = setup();
+        let modules = modules.iter().collect::<Vec<_>>();
+
+        // Node 0 is absent from the partition of the third module
+        let absent = vec![
+            HashSet::new(),
+            HashSet::new(),
+            [0].into_iter().collect(),
+            HashSet::new(),
+            HashSet::new(),
+        ];
 
-            modules.push(module);
-        }
+        let (score, penalty) = Scorer::new(1, 0)
+            .with_absent(Missing::Exclude, absent.clone())
+            .score(&module, &modules);
+        assert_eq!((score, penalty), (49, 1));
 
-        let num_partitions_to_exclude = ((1.0 - 0.95) * modules.len() as f32) as usize;
-        let penalty_weight = 10 * module.len() as i64;
-        let scorer = Scorer::new(penalty_weight, modules.len() - num_partitions_to_exclude);
+        let (score, penalty) = Scorer::new(1, 0)
+            .with_absent(Missing::Ignore, absent.clone())
+            .score(&module, &modules);
+        assert_eq!((score, penalty), (49, 0));
 
-        b.iter(|| scorer.score(&module, &modules.iter().collect::<Vec<_>>()));
+        let (score, penalty) = Scorer::new(1, 0)
+            .with_absent(Missing::Renormalize, absent)
+            .score(&module, &modules);
+        assert_eq!((score, penalty), (50, 0));
     }
 }
@@ -1,31 +1,514 @@
-use std::fs;
+use crate::bootstrap::Resampling;
+use crate::clustering::{Missing, ModuleTest};
+use crate::io::STDIO;
+use crate::optimizer::{self, CoreOptimizer, Restarts};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Optimizer {
+    Annealing,
+    Greedy,
+    GreedyAnnealing,
+    Exact,
+}
+
+impl Optimizer {
+    pub fn build(self, restarts: usize) -> Box<dyn CoreOptimizer> {
+        match self {
+            Optimizer::Annealing if restarts > 1 => {
+                Box::new(Restarts::new(optimizer::Annealing::default(), restarts))
+            }
+            Optimizer::Annealing => Box::new(optimizer::Annealing::default()),
+            Optimizer::Greedy => Box::new(optimizer::Greedy),
+            Optimizer::GreedyAnnealing if restarts > 1 => Box::new(Restarts::new(
+                optimizer::GreedyAnnealing::default(),
+                restarts,
+            )),
+            Optimizer::GreedyAnnealing => Box::new(optimizer::GreedyAnnealing::default()),
+            Optimizer::Exact => Box::new(optimizer::Exact),
+        }
+    }
+}
+
+impl std::str::FromStr for Optimizer {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "annealing" => Ok(Optimizer::Annealing),
+            "greedy" => Ok(Optimizer::Greedy),
+            "greedy-annealing" => Ok(Optimizer::GreedyAnnealing),
+            "exact" => Ok(Optimizer::Exact),
+            _ => Err("Unknown optimizer, expected annealing, greedy, greedy-annealing or exact"),
+        }
+    }
+}
+
+/// How to make significance consistent across the levels of the reference partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consistency {
+    /// Every module is clustered independently.
+    None,
+    /// Only nodes in the parent module's core are candidates for a module's core.
+    Restrict,
+    /// Nodes outside the parent module's core are removed from a module's core.
+    Propagate,
+}
+
+impl std::str::FromStr for Consistency {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Consistency::None),
+            "restrict" => Ok(Consistency::Restrict),
+            "propagate" => Ok(Consistency::Propagate),
+            _ => Err("Unknown consistency, expected none, restrict or propagate"),
+        }
+    }
+}
+
+impl std::str::FromStr for Missing {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exclude" => Ok(Missing::Exclude),
+            "ignore" => Ok(Missing::Ignore),
+            "renormalize" => Ok(Missing::Renormalize),
+            _ => Err("Unknown missing policy, expected exclude, ignore or renormalize"),
+        }
+    }
+}
+
+/// Parses `none`, `core:fraction` or `jaccard:threshold`.
+impl std::str::FromStr for ModuleTest {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| match value.parse() {
+            Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+            _ => Err("Module test threshold must be between 0 and 1"),
+        };
+
+        match s.split_once(':') {
+            None if s == "none" => Ok(ModuleTest::None),
+            Some(("core", fraction)) => Ok(ModuleTest::CoreFraction(parse(fraction)?)),
+            Some(("jaccard", threshold)) => Ok(ModuleTest::Jaccard(parse(threshold)?)),
+            _ => Err("Unknown module test, expected none, core:fraction or jaccard:threshold"),
+        }
+    }
+}
+
+/// Parses `poisson` or `gamma:cv`.
+impl std::str::FromStr for Resampling {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "poisson" => Ok(Resampling::Poisson),
+            Some(("gamma", cv)) => match cv.parse() {
+                Ok(cv) if cv > 0.0 => Ok(Resampling::Gamma { cv }),
+                _ => Err("Coefficient of variation must be a positive number"),
+            },
+            _ => Err("Unknown resampling, expected poisson or gamma:cv"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `path node` per line, with `:` after significant and `;` after insignificant modules.
+    Text,
+    Json,
+    JsonLines,
+    /// Infomap `.tree`, or `.ftree` if the input had links, with the significance of each node.
+    Tree,
+    /// The module hierarchy as nested JSON, with the nodes as leaves.
+    JsonTree,
+}
+
+impl OutputFormat {
+    /// Guesses the format from the file extension, defaulting to text.
+    pub fn from_file_name(file_name: &str) -> OutputFormat {
+        match file_name.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("json") => OutputFormat::Json,
+            Some("jsonl") => OutputFormat::JsonLines,
+            Some("tree" | "ftree") => OutputFormat::Tree,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            "tree" => Ok(OutputFormat::Tree),
+            "json-tree" => Ok(OutputFormat::JsonTree),
+            _ => Err("Unknown output format, expected text, json, jsonl, tree or json-tree"),
+        }
+    }
+}
+
+/// Confidence per level of the reference partition, starting at level 1.
+///
+/// Levels without a confidence of their own use the default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Confidence {
+    pub levels: Vec<f32>,
+    pub default: f32,
+}
+
+impl Confidence {
+    pub fn new(default: f32) -> Self {
+        Self {
+            levels: Vec::new(),
+            default,
+        }
+    }
+
+    pub fn for_level(&self, level: u8) -> f32 {
+        (level as usize)
+            .checked_sub(1)
+            .and_then(|i| self.levels.get(i))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Default for Confidence {
+    fn default() -> Self {
+        Self::new(0.95)
+    }
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for conf in self.levels.iter() {
+            write!(f, "{},", conf)?;
+        }
+        write!(f, "{} (default)", self.default)
+    }
+}
+
+fn parse_conf(arg: &str) -> Result<f32, &'static str> {
+    match arg.parse() {
+        Ok(conf) if 0.0 < conf && conf <= 1.0 => Ok(conf),
+        _ => Err("Confidence must be a number in (0, 1]"),
+    }
+}
+
+/// Parses a comma separated list of confidences, one per level.
+/// The last confidence is also used as default for deeper levels.
+impl std::str::FromStr for Confidence {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let levels = s
+            .split(',')
+            .map(parse_conf)
+            .collect::<Result<Vec<_>, _>>()?;
+        let default = *levels.last().unwrap();
+        Ok(Self { levels, default })
+    }
+}
+
+/// Parses a comma separated list of confidences to sweep, sorted and deduplicated.
+fn parse_sweep(s: &str) -> Result<Vec<f32>, &'static str> {
+    let mut sweep = s
+        .split(',')
+        .map(parse_conf)
+        .collect::<Result<Vec<_>, _>>()?;
+    sweep.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sweep.dedup();
+    Ok(sweep)
+}
 
 pub struct Config {
     pub seed: u64,
-    pub conf: f32,
+    pub conf: Confidence,
+    pub restarts: usize,
+    pub optimizer: Optimizer,
+    pub consistency: Consistency,
+    pub missing: Missing,
     pub in_file: String,
     pub out_file: String,
+    pub out_format: OutputFormat,
+    pub frequencies_file: Option<String>,
+    /// Directories or glob patterns of bootstrap partition files. If any,
+    /// the input file only holds the reference partition.
+    pub bootstraps: Vec<String>,
+    /// Write the parsed input to this cache file and exit.
+    pub cache_file: Option<String>,
+    /// Confidences to sweep in increasing order, instead of `conf`.
+    pub sweep: Vec<f32>,
+    /// Fail instead of warn if the partitions look broken.
+    pub strict: bool,
+    /// Write bootstrap replicates of the network in the input file to this
+    /// directory and exit.
+    pub replicates_dir: Option<String>,
+    pub replicates: usize,
+    pub resampling: Resampling,
+    /// Read a network instead of partitions, and partition it and its
+    /// bootstrap replicates with the built-in partitioner.
+    pub from_network: bool,
+    /// Compare the result in the input file to the result in this file and
+    /// write the differences.
+    pub diff_file: Option<String>,
+    /// Write the similarity of each bootstrap partition to the reference on
+    /// each level to this file.
+    pub similarity_file: Option<String>,
+    pub module_test: ModuleTest,
 }
 
 impl Config {
+    /// Parses `[in_file] [out_file] [options]`, where missing files or
+    /// [`STDIO`] mean stdin and stdout.
     pub fn new(mut args: std::env::Args) -> Result<Config, &'static str> {
         args.next();
 
-        let in_file = match args.next() {
-            Some(arg) => fs::read_to_string(arg).expect("Failed to read input file"),
-            None => return Err("Didn't get input file"),
-        };
+        let mut files = Vec::new();
+        let mut restarts = 1;
+        let mut frequencies_file = None;
+        let mut optimizer = Optimizer::Annealing;
+        let mut conf = None;
+        let mut consistency = Consistency::None;
+        let mut missing = Missing::Exclude;
+        let mut out_format = None;
+        let mut bootstraps = Vec::new();
+        let mut cache_file = None;
+        let mut default_conf = None;
+        let mut sweep = Vec::new();
+        let mut strict = false;
+        let mut from_network = false;
+        let mut seed = 123;
+        let mut replicates_dir = None;
+        let mut replicates = 100;
+        let mut resampling = Resampling::Poisson;
+        let mut diff_file = None;
+        let mut similarity_file = None;
+        let mut module_test = ModuleTest::None;
 
-        let out_file = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get output file"),
-        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--restarts" => {
+                    restarts = match args.next().and_then(|arg| arg.parse().ok()) {
+                        Some(restarts) if restarts > 0 => restarts,
+                        _ => return Err("--restarts requires a positive integer"),
+                    };
+                }
+                "--frequencies" => {
+                    frequencies_file = match args.next() {
+                        Some(arg) => Some(arg),
+                        None => return Err("--frequencies requires an output file"),
+                    };
+                }
+                "--optimizer" => {
+                    optimizer = match args.next() {
+                        Some(arg) => arg.parse()?,
+                        None => return Err("--optimizer requires a name"),
+                    };
+                }
+                "--conf" => {
+                    conf = match args.next() {
+                        Some(arg) => Some(arg.parse()?),
+                        None => return Err("--conf requires a confidence per level"),
+                    };
+                }
+                "--default-conf" => {
+                    default_conf = match args.next() {
+                        Some(arg) => Some(parse_conf(&arg)?),
+                        None => return Err("--default-conf requires a confidence"),
+                    };
+                }
+                "--consistency" => {
+                    consistency = match args.next() {
+                        Some(arg) => arg.parse()?,
+                        None => return Err("--consistency requires a mode"),
+                    };
+                }
+                "--missing" => {
+                    missing = match args.next() {
+                        Some(arg) => arg.parse()?,
+                        None => return Err("--missing requires a policy"),
+                    };
+                }
+                "--format" => {
+                    out_format = match args.next() {
+                        Some(arg) => Some(arg.parse()?),
+                        None => return Err("--format requires an output format"),
+                    };
+                }
+                "--bootstraps" => match args.next() {
+                    Some(arg) => bootstraps.push(arg),
+                    None => return Err("--bootstraps requires a directory or glob pattern"),
+                },
+                "--write-cache" => {
+                    cache_file = match args.next() {
+                        Some(arg) => Some(arg),
+                        None => return Err("--write-cache requires an output file"),
+                    };
+                }
+                "--sweep" => {
+                    sweep = match args.next() {
+                        Some(arg) => parse_sweep(&arg)?,
+                        None => return Err("--sweep requires a list of confidences"),
+                    };
+                }
+                "--strict" => strict = true,
+                "--from-network" => from_network = true,
+                "--seed" => {
+                    seed = match args.next().and_then(|arg| arg.parse().ok()) {
+                        Some(seed) => seed,
+                        None => return Err("--seed requires a non-negative integer"),
+                    };
+                }
+                "--write-replicates" => {
+                    replicates_dir = match args.next() {
+                        Some(arg) => Some(arg),
+                        None => return Err("--write-replicates requires an output directory"),
+                    };
+                }
+                "--replicates" => {
+                    replicates = match args.next().and_then(|arg| arg.parse().ok()) {
+                        Some(replicates) if replicates > 0 => replicates,
+                        _ => return Err("--replicates requires a positive integer"),
+                    };
+                }
+                "--resampling" => {
+                    resampling = match args.next() {
+                        Some(arg) => arg.parse()?,
+                        None => return Err("--resampling requires a method"),
+                    };
+                }
+                "--diff" => {
+                    diff_file = match args.next() {
+                        Some(arg) => Some(arg),
+                        None => return Err("--diff requires a result file"),
+                    };
+                }
+                "--similarity" => {
+                    similarity_file = match args.next() {
+                        Some(arg) => Some(arg),
+                        None => return Err("--similarity requires an output file"),
+                    };
+                }
+                "--module-test" => {
+                    module_test = match args.next() {
+                        Some(arg) => arg.parse()?,
+                        None => return Err("--module-test requires a test"),
+                    };
+                }
+                _ if arg.starts_with("--") => return Err("Unknown argument"),
+                _ if files.len() < 2 => files.push(arg),
+                _ => return Err("Too many files, expected input and output file"),
+            }
+        }
+
+        let mut files = files.into_iter();
+        let in_file = files.next().unwrap_or_else(|| STDIO.to_owned());
+        let out_file = files.next().unwrap_or_else(|| STDIO.to_owned());
+
+        // The sweep writes its own format with the confidences it was given
+        if !sweep.is_empty() {
+            if conf.is_some() || default_conf.is_some() {
+                return Err("--sweep cannot be combined with --conf or --default-conf");
+            }
+            if out_format.is_some() {
+                return Err("--sweep cannot be combined with --format");
+            }
+            if frequencies_file.is_some() {
+                return Err("--sweep cannot be combined with --frequencies");
+            }
+        }
+
+        let out_format = out_format.unwrap_or_else(|| OutputFormat::from_file_name(&out_file));
+
+        let mut conf: Confidence = conf.unwrap_or_default();
+        if let Some(default_conf) = default_conf {
+            conf.default = default_conf;
+        }
+
+        // The deterministic optimizers give the same core on every restart
+        if restarts > 1 && matches!(optimizer, Optimizer::Greedy | Optimizer::Exact) {
+            return Err("--restarts requires the annealing or greedy-annealing optimizer");
+        }
+
+        // Frequencies are counted across restarts
+        if frequencies_file.is_some() && restarts < 2 {
+            return Err("--frequencies requires --restarts greater than 1");
+        }
 
         Ok(Config {
-            seed: 123,
-            conf: 0.95,
+            seed,
+            conf,
+            restarts,
+            optimizer,
+            consistency,
+            missing,
             in_file,
             out_file,
+            out_format,
+            frequencies_file,
+            bootstraps,
+            cache_file,
+            sweep,
+            strict,
+            replicates_dir,
+            replicates,
+            resampling,
+            from_network,
+            diff_file,
+            similarity_file,
+            module_test,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_confidence() {
+        let conf: Confidence = "0.95,0.9,0.8".parse().unwrap();
+        assert_eq!(conf.for_level(1), 0.95);
+        assert_eq!(conf.for_level(2), 0.9);
+        assert_eq!(conf.for_level(3), 0.8);
+        assert_eq!(conf.for_level(4), 0.8);
+
+        let conf: Confidence = "0.9".parse().unwrap();
+        assert_eq!(conf.for_level(1), 0.9);
+        assert_eq!(conf.for_level(5), 0.9);
+
+        assert!("0.9,1.5".parse::<Confidence>().is_err());
+        assert!("0.9,".parse::<Confidence>().is_err());
+    }
+
+    #[test]
+    fn test_parse_resampling() {
+        assert_eq!("poisson".parse(), Ok(Resampling::Poisson));
+        assert_eq!("gamma:0.2".parse(), Ok(Resampling::Gamma { cv: 0.2 }));
+        assert!("gamma".parse::<Resampling>().is_err());
+        assert!("gamma:0".parse::<Resampling>().is_err());
+    }
+
+    #[test]
+    fn test_parse_module_test() {
+        assert_eq!("none".parse(), Ok(ModuleTest::None));
+        assert_eq!("core:0.5".parse(), Ok(ModuleTest::CoreFraction(0.5)));
+        assert_eq!("jaccard:0.3".parse(), Ok(ModuleTest::Jaccard(0.3)));
+        assert!("jaccard:2".parse::<ModuleTest>().is_err());
+        assert!("core".parse::<ModuleTest>().is_err());
+    }
+
+    #[test]
+    fn test_parse_sweep() {
+        assert_eq!(parse_sweep("0.9,0.5,0.7,0.9"), Ok(vec![0.5, 0.7, 0.9]));
+        assert!(parse_sweep("0.5,0").is_err());
+    }
+}
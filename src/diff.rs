@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+use crate::io::cmp_module_ids;
+use crate::{HashMap, HashSet, Module, NodeId};
+
+/// How well two results agree on one level of the reference partition, over
+/// all nodes of all modules on the level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Agreement {
+    pub nodes: usize,
+    pub both: usize,
+    pub first_only: usize,
+    pub second_only: usize,
+}
+
+impl Agreement {
+    /// Fraction of nodes with the same significance in both results.
+    pub fn fraction(&self) -> f64 {
+        match self.nodes {
+            0 => 1.0,
+            nodes => 1.0 - (self.first_only + self.second_only) as f64 / nodes as f64,
+        }
+    }
+
+    /// Jaccard index of the significant nodes in the two results.
+    pub fn jaccard(&self) -> f64 {
+        match self.both + self.first_only + self.second_only {
+            0 => 1.0,
+            union => self.both as f64 / union as f64,
+        }
+    }
+}
+
+/// A node that is significant in one result but not the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub module_id: String,
+    pub node: NodeId,
+    pub first: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    /// Agreement per level.
+    pub levels: BTreeMap<u8, Agreement>,
+    /// Nodes whose significance changed, by module and node.
+    pub changes: Vec<Change>,
+    /// Modules whose core size changed, with the sizes in both results.
+    pub core_sizes: Vec<(String, usize, usize)>,
+    /// Modules whose significance changed, and whether they are significant
+    /// in the first result.
+    pub module_changes: Vec<(String, bool)>,
+    /// Modules with a core in only one of the results.
+    pub unmatched: Vec<String>,
+}
+
+/// Compares the significant cores of two results for the same reference
+/// partition, for example with different confidences, seeds or optimizers.
+///
+/// Modules with a core in only one result, or missing from `modules`, are
+/// listed as unmatched and left out of the agreement. Modules are significant
+/// unless listed in `first_insignificant` or `second_insignificant`.
+pub fn diff(
+    modules: &HashMap<String, Module>,
+    first: &HashMap<String, HashSet<NodeId>>,
+    second: &HashMap<String, HashSet<NodeId>>,
+    first_insignificant: &HashSet<String>,
+    second_insignificant: &HashSet<String>,
+) -> Diff {
+    let mut diff = Diff::default();
+
+    let mut module_ids = first
+        .keys()
+        .chain(
+            second
+                .keys()
+                .filter(|module_id| !first.contains_key(*module_id)),
+        )
+        .collect::<Vec<_>>();
+    module_ids.sort_by(|a, b| cmp_module_ids(a, b));
+
+    for module_id in module_ids {
+        let (module, first_core, second_core) = match (
+            modules.get(module_id),
+            first.get(module_id),
+            second.get(module_id),
+        ) {
+            (Some(module), Some(first), Some(second)) => (module, first, second),
+            _ => {
+                diff.unmatched.push(module_id.clone());
+                continue;
+            }
+        };
+
+        let agreement = diff.levels.entry(module.level).or_default();
+
+        let mut nodes = module.nodes.iter().copied().collect::<Vec<_>>();
+        nodes.sort_unstable();
+
+        for node in nodes {
+            agreement.nodes += 1;
+
+            match (first_core.contains(&node), second_core.contains(&node)) {
+                (true, true) => agreement.both += 1,
+                (false, false) => {}
+                (first, _) => {
+                    if first {
+                        agreement.first_only += 1;
+                    } else {
+                        agreement.second_only += 1;
+                    }
+
+                    diff.changes.push(Change {
+                        module_id: module_id.clone(),
+                        node,
+                        first,
+                    });
+                }
+            }
+        }
+
+        if first_core.len() != second_core.len() {
+            diff.core_sizes
+                .push((module_id.clone(), first_core.len(), second_core.len()));
+        }
+
+        let first_significant = !first_insignificant.contains(module_id);
+        if first_significant == second_insignificant.contains(module_id) {
+            diff.module_changes
+                .push((module_id.clone(), first_significant));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let (networks, labels) = crate::io::read_input("a 1:1\nb 1:1\nc 1:2\nd 2\n").unwrap();
+        let modules = &networks[&0].modules;
+        let node = |label| labels.get(label).unwrap();
+
+        let first = modules
+            .iter()
+            .map(|(module_id, module)| (module_id.clone(), module.nodes.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let mut second = first.clone();
+        second.get_mut("1:1").unwrap().remove(&node("b"));
+        second.remove("2");
+
+        let diff = diff(
+            modules,
+            &first,
+            &second,
+            &HashSet::new(),
+            &HashSet::from(["1".to_owned(), "2".to_owned()]),
+        );
+
+        assert_eq!(diff.unmatched, vec!["2".to_owned()]);
+        assert_eq!(
+            diff.changes,
+            vec![Change {
+                module_id: "1:1".to_owned(),
+                node: node("b"),
+                first: true
+            }]
+        );
+        assert_eq!(diff.core_sizes, vec![("1:1".to_owned(), 2, 1)]);
+        assert_eq!(diff.module_changes, vec![("1".to_owned(), true)]);
+
+        let level1 = diff.levels[&1];
+        assert_eq!(level1.nodes, 3);
+        assert_eq!(level1.fraction(), 1.0);
+
+        let level2 = diff.levels[&2];
+        assert_eq!((level2.nodes, level2.both, level2.first_only), (3, 2, 1));
+        assert_eq!(level2.jaccard(), 2.0 / 3.0);
+    }
+}
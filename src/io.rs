@@ -1,41 +1,499 @@
 use std::collections::BTreeMap;
 use std::fs::File;
+use std::io::BufRead;
 use std::io::BufWriter;
 use std::io::Write;
-use std::num::ParseIntError;
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::{HashMap, HashSet, Module, Network, NetworkId, NodeId};
 
-pub fn read_input(in_file: &str) -> Result<BTreeMap<NetworkId, Network>, ParseIntError> {
+/// Reads from stdin or writes to stdout in place of a file.
+pub const STDIO: &str = "-";
+
+/// Creates `file_name` for buffered writing, or writes to stdout if it is [`STDIO`].
+pub fn create(file_name: &str) -> std::io::Result<Box<dyn Write>> {
+    if file_name == STDIO {
+        Ok(Box::new(BufWriter::new(std::io::stdout())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(file_name)?)))
+    }
+}
+
+/// Maps arbitrary node labels to dense node ids and back.
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    labels: Vec<String>,
+    ids: HashMap<String, NodeId>,
+}
+
+impl Labels {
+    pub fn new() -> Labels {
+        Labels::default()
+    }
+
+    /// Returns the id of `label`, assigning the next free id if it is new.
+    pub fn intern(&mut self, label: &str) -> NodeId {
+        if let Some(&id) = self.ids.get(label) {
+            return id;
+        }
+
+        let id = self.labels.len() as NodeId;
+        self.labels.push(label.to_owned());
+        self.ids.insert(label.to_owned(), id);
+        id
+    }
+
+    pub fn get(&self, label: &str) -> Option<NodeId> {
+        self.ids.get(label).copied()
+    }
+
+    pub fn label(&self, id: NodeId) -> &str {
+        &self.labels[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+/// Orders numeric labels numerically and before all other labels.
+pub fn cmp_labels(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Orders module ids level by level with [`cmp_labels`], parents first.
+pub fn cmp_module_ids(a: &str, b: &str) -> std::cmp::Ordering {
+    a.split(':')
+        .zip(b.split(':'))
+        .map(|(a, b)| cmp_labels(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Marks a node as absent from the partition in that column.
+pub const MISSING: &str = "-";
+
+pub(crate) fn add_path(network: &mut Network, path: &str, node_id: NodeId) {
+    if network.nodes.contains(&node_id) {
+        network.duplicates.insert(node_id);
+    }
+
+    // 1:2:3 -> [1, 1:2, 1:2:3]
+    for (end, _) in path.match_indices(':') {
+        network.add_node(&path[..end], node_id);
+    }
+
+    network.add_node(path, node_id);
+}
+
+/// Input formats for partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One row per node, with the node id followed by one module path per partition.
+    Columns,
+    /// Infomap `.tree`.
+    Tree,
+    /// Infomap `.ftree`, a `.tree` followed by the links between modules.
+    Ftree,
+    /// Infomap `.clu`, with one `node module flow` row per node.
+    Clu,
+    /// JSON object mapping node ids to paths, or `{reference, bootstraps}`
+    /// with one such object per partition.
+    Json,
+}
+
+impl Format {
+    /// Detects the format from the file extension, or else from the contents.
+    pub fn detect(file_name: &str, contents: &str) -> Format {
+        match file_name.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("tree") => return Format::Tree,
+            Some("ftree") => return Format::Ftree,
+            Some("clu") => return Format::Clu,
+            Some("json") => return Format::Json,
+            _ => {}
+        }
+
+        if contents.trim_start().starts_with('{') {
+            return Format::Json;
+        }
+
+        let mut format = Format::Columns;
+
+        for line in contents.lines() {
+            if line.starts_with("*Links") {
+                return Format::Ftree;
+            }
+
+            if line.starts_with('#') {
+                if line.contains("path flow name") {
+                    format = Format::Tree;
+                } else if line.contains("node_id module") || line.contains("node module") {
+                    format = Format::Clu;
+                }
+            } else if format == Format::Columns {
+                break;
+            }
+        }
+
+        format
+    }
+}
+
+/// Opens `file_name` for buffered reading, decompressing gzip and zstd input.
+/// Reads from stdin if `file_name` is [`STDIO`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(file_name: &str) -> Result<Box<dyn BufRead>, Box<dyn std::error::Error>> {
+    use std::io::BufReader;
+
+    if file_name == STDIO {
+        decompress(BufReader::new(std::io::stdin()))
+    } else {
+        decompress(BufReader::new(File::open(file_name)?))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn decompress(
+    mut reader: impl BufRead + 'static,
+) -> Result<Box<dyn BufRead>, Box<dyn std::error::Error>> {
+    use std::io::BufReader;
+
+    const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+    const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+    let magic = reader.fill_buf()?;
+
+    if magic.starts_with(GZIP_MAGIC) {
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads a single partition from a possibly compressed file in any format.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_file(
+    file_name: &str,
+    labels: &mut Labels,
+) -> Result<Network, Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let mut reader = open(file_name)?;
+    let format = detect_format(file_name, &mut reader)?;
+
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+
+    read_partition(&contents, format, labels)
+        .map_err(|err| format!("{}: {}", file_name, err).into())
+}
+
+/// Reads a result written by [`write_result`] from a possibly compressed file.
+/// Results in other output formats are rejected.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_result_file(
+    file_name: &str,
+    labels: &mut Labels,
+) -> Result<(Network, SignificantCores, HashSet<String>), Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let mut contents = String::new();
+    open(file_name)?.read_to_string(&mut contents)?;
+
+    // Text results have no header, so they look like columns
+    let format = Format::detect(file_name, &contents);
+    if format != Format::Columns {
+        return Err(format!(
+            "{}: only text results can be compared, not {:?}",
+            file_name, format
+        )
+        .into());
+    }
+
+    read_result(&contents, labels).map_err(|err| format!("{}: {}", file_name, err).into())
+}
+
+/// The files in a directory, or the files matching a glob pattern, sorted by name.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find_files(pattern: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(pattern);
+
+    let mut files = if path.is_dir() {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?
+    };
+
+    files.retain(|file| file.is_file());
+    files.sort();
+
+    Ok(files
+        .into_iter()
+        .map(|file| file.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Nodes that differ between the reference partition and a bootstrap partition.
+#[derive(Debug, Clone, Default)]
+pub struct Mismatch {
+    /// Reference nodes absent from the bootstrap partition.
+    pub missing: Vec<NodeId>,
+    /// Nodes in the bootstrap partition that are not in the reference.
+    pub unknown: Vec<NodeId>,
+}
+
+/// Removes nodes that are not in `reference` from `network` and reports them,
+/// together with the reference nodes that are absent from `network`.
+pub fn join(reference: &Network, network: &mut Network) -> Mismatch {
+    let mut missing = reference
+        .nodes
+        .difference(&network.nodes)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let mut unknown = network
+        .nodes
+        .difference(&reference.nodes)
+        .copied()
+        .collect::<Vec<_>>();
+
+    missing.sort_unstable();
+    unknown.sort_unstable();
+
+    if !unknown.is_empty() {
+        network.retain_nodes(&reference.nodes);
+    }
+
+    Mismatch { missing, unknown }
+}
+
+/// Detects the format of a possibly compressed file from its name, or else
+/// from the start of `reader` without consuming it.
+pub fn detect_format(
+    file_name: &str,
+    reader: &mut impl BufRead,
+) -> Result<Format, Box<dyn std::error::Error>> {
+    let file_name = file_name.trim_end_matches(".gz").trim_end_matches(".zst");
+    let start = String::from_utf8_lossy(reader.fill_buf()?);
+    Ok(Format::detect(file_name, &start))
+}
+
+/// Links between modules, from the link section of an `.ftree` file.
+#[derive(Debug, Clone, Default)]
+pub struct Links {
+    /// `directed` or `undirected`.
+    pub link_type: String,
+    pub modules: Vec<ModuleLinks>,
+}
+
+/// The links between the children of one module, `root` for the top level.
+#[derive(Debug, Clone)]
+pub struct ModuleLinks {
+    pub module_id: String,
+    pub enter_flow: f64,
+    pub exit_flow: f64,
+    pub num_children: usize,
+    /// `(source, target, flow)` where source and target are child ranks.
+    pub links: Vec<(u32, u32, f64)>,
+}
+
+/// A node in an Infomap tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaf {
+    /// Last segment of the node's path.
+    pub rank: String,
+    pub flow: f64,
+    pub name: String,
+}
+
+/// The nodes of an Infomap tree, so that the tree can be written back.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    /// Comment lines before the nodes.
+    pub header: Vec<String>,
+    pub leaves: HashMap<NodeId, Leaf>,
+}
+
+pub fn read_input(
+    in_file: &str,
+) -> Result<(BTreeMap<NetworkId, Network>, Labels), Box<dyn std::error::Error>> {
+    let mut labels = Labels::new();
+    let networks = read_columns(in_file, &mut labels)?;
+    Ok((networks, labels))
+}
+
+/// Reads a single partition in any format except `Columns` with more than
+/// one partition.
+pub fn read_partition(
+    contents: &str,
+    format: Format,
+    labels: &mut Labels,
+) -> Result<Network, Box<dyn std::error::Error>> {
+    match format {
+        Format::Columns => {
+            let mut networks = read_columns(contents, labels)?;
+            match networks.len() {
+                1 => Ok(networks.remove(&0).unwrap()),
+                n => Err(format!("Expected one partition, got {}", n).into()),
+            }
+        }
+        Format::Tree | Format::Ftree => read_tree(contents, labels),
+        Format::Clu => read_clu(contents, labels),
+        Format::Json => {
+            let partition: JsonPartition = serde_json::from_str(contents)?;
+            json_to_network(partition, labels)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonLabel {
+    Number(u64),
+    String(String),
+}
+
+/// Node ids mapped to paths, `null` for absent nodes.
+type JsonPartition = BTreeMap<String, Option<Vec<JsonLabel>>>;
+
+#[derive(Deserialize)]
+struct JsonInput {
+    reference: JsonPartition,
+    bootstraps: Vec<JsonPartition>,
+}
+
+fn json_to_network(
+    partition: JsonPartition,
+    labels: &mut Labels,
+) -> Result<Network, Box<dyn std::error::Error>> {
+    let mut network = Network::new();
+
+    for (node, path) in partition.into_iter() {
+        let path = match path {
+            Some(path) if !path.is_empty() => path,
+            Some(_) => return Err(format!("Node {}: empty path", node).into()),
+            None => continue,
+        };
+
+        let path = path
+            .into_iter()
+            .map(|label| match label {
+                JsonLabel::Number(label) => Ok(label.to_string()),
+                JsonLabel::String(label) if !label.contains(':') => Ok(label),
+                JsonLabel::String(label) => Err(format!("Module label {} contains ':'", label)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        add_path(&mut network, &path.join(":"), labels.intern(&node));
+    }
+
+    Ok(network)
+}
+
+/// Reads the reference partition and the bootstrap partitions from JSON, as
+/// `{"reference": {"node": [1, 2], ...}, "bootstraps": [{"node": [1], ...}, ...]}`.
+pub fn read_json(
+    contents: &str,
+) -> Result<(BTreeMap<NetworkId, Network>, Labels), Box<dyn std::error::Error>> {
+    read_json_from(contents.as_bytes())
+}
+
+/// Like [`read_json`], but parses directly from `reader`.
+pub fn read_json_from(
+    reader: impl BufRead,
+) -> Result<(BTreeMap<NetworkId, Network>, Labels), Box<dyn std::error::Error>> {
+    let input: JsonInput = serde_json::from_reader(reader)?;
+    let mut labels = Labels::new();
+    let mut networks = BTreeMap::new();
+
+    networks.insert(0, json_to_network(input.reference, &mut labels)?);
+
+    for (i, bootstrap) in input.bootstraps.into_iter().enumerate() {
+        networks.insert(i + 1, json_to_network(bootstrap, &mut labels)?);
+    }
+
+    Ok((networks, labels))
+}
+
+pub fn read_columns(
+    in_file: &str,
+    labels: &mut Labels,
+) -> Result<BTreeMap<NetworkId, Network>, Box<dyn std::error::Error>> {
+    read_columns_from(in_file.as_bytes(), labels)
+}
+
+/// Like [`read_columns`], but reads one line at a time from `reader`.
+pub fn read_columns_from(
+    mut reader: impl BufRead,
+    labels: &mut Labels,
+) -> Result<BTreeMap<NetworkId, Network>, Box<dyn std::error::Error>> {
     let mut networks = BTreeMap::new();
+    let mut num_cols = None;
+    let mut line = String::new();
+    let mut line_number = 0;
+
+    loop {
+        line.clear();
+        line_number += 1;
+
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
 
-    for line in in_file.lines() {
         if line.starts_with('#') {
             continue;
         }
 
-        let cols = line.split_whitespace().collect::<Vec<_>>();
+        let len = line.split_whitespace().count();
 
-        if cols.len() < 2 {
+        if len < 2 {
             continue;
         }
 
+        // Every row must have a column for every partition
+        match num_cols {
+            None => num_cols = Some(len),
+            Some(num_cols) if num_cols != len => {
+                return Err(format!(
+                    "Line {}: expected {} partitions, got {} (use {} for absent nodes)",
+                    line_number,
+                    num_cols - 1,
+                    len - 1,
+                    MISSING
+                )
+                .into())
+            }
+            _ => {}
+        }
+
+        let mut cols = line.split_whitespace();
+
         // first column is the node id
-        let node_id = cols.first().unwrap().parse()?;
+        let node_id = labels.intern(cols.next().unwrap());
 
         // all other columns are partitions
-        for (network_id, col) in cols.into_iter().skip(1).enumerate() {
+        for (network_id, col) in cols.enumerate() {
             let network = networks.entry(network_id).or_insert_with(Network::new);
 
-            let path = col.split(':');
-            let len = path.clone().count();
-
-            // 1:2:3 -> [1, 1:2, 1:2:3]
-            for level in 1..=len {
-                let module_id = path.clone().take(level).join(":");
-                network.add_node(&module_id, node_id);
+            if col != MISSING {
+                add_path(network, col, node_id);
             }
         }
     }
@@ -43,46 +501,1372 @@ pub fn read_input(in_file: &str) -> Result<BTreeMap<NetworkId, Network>, ParseIn
     Ok(networks)
 }
 
-pub fn write_result(
-    modules: &HashMap<String, Module>,
+/// Reads a single partition in Infomap's `.tree` format, where each line is
+/// `path flow name node` and the last path segment is the node's rank.
+/// Columns after the node, such as those added by [`write_tree`], are ignored.
+///
+/// The link section of an `.ftree` file is kept in [`Network::links`].
+pub fn read_tree(
+    contents: &str,
+    labels: &mut Labels,
+) -> Result<Network, Box<dyn std::error::Error>> {
+    let mut network = Network::new();
+    let mut tree = Tree::default();
+    let mut lines = contents.lines().enumerate().peekable();
+
+    while let Some((line_number, line)) = lines.next_if(|(_, line)| !line.starts_with("*Links")) {
+        if line.starts_with('#') {
+            if tree.leaves.is_empty() {
+                tree.header.push(line.to_owned());
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (path, rest) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((line, ""));
+        let rest = rest.trim_start();
+
+        // Either `path node` or `path flow name node`, where the name may be
+        // quoted and contain whitespace
+        let (flow, name, node) = match rest.split_once(char::is_whitespace) {
+            None if rest.is_empty() => {
+                return Err(format!("Line {}: expected path and node", line_number + 1).into())
+            }
+            None => (None, None, rest),
+            Some((flow, rest)) => {
+                let rest = rest.trim_start();
+                let (name, rest) = match rest.strip_prefix('"') {
+                    Some(quoted) => match quoted.split_once('"') {
+                        Some((name, rest)) => (Some(name), rest),
+                        None => {
+                            return Err(
+                                format!("Line {}: unterminated name", line_number + 1).into()
+                            )
+                        }
+                    },
+                    None => {
+                        let (name, rest) =
+                            rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                        (Some(name), rest)
+                    }
+                };
+
+                match rest.split_whitespace().next() {
+                    Some(node) => (Some(flow), name, node),
+                    // Without a name, the last column is the node
+                    None => (Some(flow), None, name.unwrap()),
+                }
+            }
+        };
+
+        let (module_path, rank) = match path.rsplit_once(':') {
+            Some(split) => split,
+            None => return Err(format!("Line {}: path without module", line_number + 1).into()),
+        };
+
+        let node_id = labels.intern(node);
+        add_path(&mut network, module_path, node_id);
+
+        let flow = match flow.map(str::parse) {
+            Some(Ok(flow)) => flow,
+            Some(Err(_)) => return Err(format!("Line {}: invalid flow", line_number + 1).into()),
+            None => 0.0,
+        };
+
+        let name = match name {
+            Some(name) if !name.is_empty() => name.to_owned(),
+            _ => node.to_owned(),
+        };
+
+        tree.leaves.insert(
+            node_id,
+            Leaf {
+                rank: rank.to_owned(),
+                flow,
+                name,
+            },
+        );
+    }
+
+    network.tree = Some(tree);
+
+    if lines.peek().is_some() {
+        network.links = Some(read_links(lines)?);
+    }
+
+    Ok(network)
+}
+
+fn read_links<'a>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+) -> Result<Links, Box<dyn std::error::Error>> {
+    let mut links = Links::default();
+
+    let error = |line_number: usize| format!("Line {}: invalid link section", line_number + 1);
+
+    for (line_number, line) in lines {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let cols = line.split_whitespace().collect::<Vec<_>>();
+
+        match cols.as_slice() {
+            ["*Links", link_type] => links.link_type = link_type.to_string(),
+            ["*Links", module_id, enter_flow, exit_flow, _, num_children] => {
+                links.modules.push(ModuleLinks {
+                    module_id: module_id.to_string(),
+                    enter_flow: enter_flow.parse().map_err(|_| error(line_number))?,
+                    exit_flow: exit_flow.parse().map_err(|_| error(line_number))?,
+                    num_children: num_children.parse().map_err(|_| error(line_number))?,
+                    links: Vec::new(),
+                })
+            }
+            [source, target, flow] => {
+                let module = links.modules.last_mut().ok_or_else(|| error(line_number))?;
+                module.links.push((
+                    source.parse().map_err(|_| error(line_number))?,
+                    target.parse().map_err(|_| error(line_number))?,
+                    flow.parse().map_err(|_| error(line_number))?,
+                ));
+            }
+            _ => return Err(error(line_number).into()),
+        }
+    }
+
+    Ok(links)
+}
+
+/// Reads a single partition in Infomap's `.clu` format, where each line is
+/// `node module flow`.
+pub fn read_clu(
+    contents: &str,
+    labels: &mut Labels,
+) -> Result<Network, Box<dyn std::error::Error>> {
+    let mut network = Network::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let mut cols = line.split_whitespace();
+
+        let (node, module) = match (cols.next(), cols.next()) {
+            (Some(node), Some(module)) => (node, module),
+            _ => return Err(format!("Line {}: expected node and module", line_number + 1).into()),
+        };
+
+        add_path(&mut network, module, labels.intern(node));
+    }
+
+    Ok(network)
+}
+
+/// Significant nodes of each module.
+pub type SignificantCores = HashMap<String, HashSet<NodeId>>;
+
+/// Reads a result written by [`write_result`], where each line is `path node`
+/// and each module in the path is followed by `:` if the node is significant
+/// in it and `;` if not. The last module has no separator if significant.
+///
+/// Also returns the modules listed in the `# insignificant modules` comment.
+pub fn read_result(
+    contents: &str,
+    labels: &mut Labels,
+) -> Result<(Network, SignificantCores, HashSet<String>), Box<dyn std::error::Error>> {
+    let mut network = Network::new();
+    let mut significant_cores = HashMap::new();
+    let mut insignificant_modules = HashSet::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        if let Some(module_ids) = line.strip_prefix("# insignificant modules ") {
+            insignificant_modules.extend(module_ids.split_whitespace().map(str::to_owned));
+            continue;
+        }
+
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let mut cols = line.split_whitespace();
+
+        // Sweep, frequencies, similarity and diff output have more columns
+        let (path, node) = match (cols.next(), cols.next(), cols.next()) {
+            (Some(path), Some(node), None) => (path, labels.intern(node)),
+            _ => {
+                return Err(format!(
+                    "Line {}: expected path and node, only text results can be read",
+                    line_number + 1
+                )
+                .into())
+            }
+        };
+
+        let mut module_id = String::with_capacity(path.len());
+
+        for segment in path.split_inclusive([':', ';']) {
+            let (label, significant) = match segment.strip_suffix(';') {
+                Some(label) => (label, false),
+                None => (segment.strip_suffix(':').unwrap_or(segment), true),
+            };
+
+            if label.is_empty() {
+                return Err(format!("Line {}: empty module in {}", line_number + 1, path).into());
+            }
+
+            if !module_id.is_empty() {
+                module_id.push(':');
+            }
+            module_id.push_str(label);
+
+            network.add_node(&module_id, node);
+
+            let core = significant_cores
+                .entry(module_id.clone())
+                .or_insert_with(HashSet::new);
+
+            if significant {
+                core.insert(node);
+            }
+        }
+    }
+
+    Ok((network, significant_cores, insignificant_modules))
+}
+
+/// Writes the links between modules in the `.ftree` link section format.
+pub fn write_links(f: &mut impl Write, links: &Links) -> std::io::Result<()> {
+    writeln!(f, "*Links {}", links.link_type)?;
+    writeln!(f, "#*Links path enterFlow exitFlow numEdges numChildren")?;
+
+    for module in links.modules.iter() {
+        writeln!(
+            f,
+            "*Links {} {} {} {} {}",
+            module.module_id,
+            module.enter_flow,
+            module.exit_flow,
+            module.links.len(),
+            module.num_children
+        )?;
+
+        for (source, target, flow) in module.links.iter() {
+            writeln!(f, "{} {} {}", source, target, flow)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The modules of each node in the reference partition, with their significance,
+/// sorted by top module and node.
+fn node_paths<'a>(
+    modules: &'a HashMap<String, Module>,
     significant_cores: &HashMap<String, HashSet<NodeId>>,
-    out_file: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    labels: &Labels,
+) -> Vec<(NodeId, Vec<(&'a Module, bool)>)> {
     let mut nodes = BTreeMap::new();
 
     for (module_id, core) in significant_cores.iter() {
         let module = &modules[module_id];
 
-        for node in module.nodes.iter() {
-            let significant = core.contains(node);
+        for &node in module.nodes.iter() {
+            let significant = core.contains(&node);
             nodes
                 .entry(node)
                 .or_insert_with(BTreeMap::new)
-                .insert(module.level, (module.module, significant));
+                .insert(module.level, (module, significant));
         }
     }
 
-    let mut nodes = nodes.into_iter().collect::<Vec<_>>();
+    let mut nodes = nodes
+        .into_iter()
+        .map(|(node, entries)| (node, entries.into_values().collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
 
     // Sort by top module id
-    nodes.sort_unstable_by_key(|(_, entries)| (*entries.first_key_value().unwrap().1).0);
+    nodes.sort_by(|(node1, entries1), (node2, entries2)| {
+        let module1 = &entries1.first().unwrap().0.label;
+        let module2 = &entries2.first().unwrap().0.label;
+        cmp_labels(module1, module2)
+            .then_with(|| cmp_labels(labels.label(*node1), labels.label(*node2)))
+    });
 
-    let mut f = BufWriter::new(File::create(out_file)?);
+    nodes
+}
+
+pub fn write_result(
+    modules: &HashMap<String, Module>,
+    significant_cores: &HashMap<String, HashSet<NodeId>>,
+    insignificant_modules: &HashSet<String>,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let nodes = node_paths(modules, significant_cores, labels);
+
+    write_insignificant_modules(f, "insignificant modules", insignificant_modules)?;
 
     for (node, entries) in nodes.iter() {
-        let mut path = String::with_capacity(2 * entries.values().len());
+        let mut path = String::with_capacity(2 * entries.len());
 
-        for &(module, significant) in entries.values() {
+        for &(module, significant) in entries.iter() {
             let separator = if significant { ':' } else { ';' };
-            path.push_str(&format!("{}{}", module, separator));
+            path.push_str(&format!("{}{}", module.label, separator));
         }
 
         if path.ends_with(':') {
             path.pop();
         }
 
-        writeln!(f, "{} {}", path, node)?;
+        writeln!(f, "{} {}", path, labels.label(*node))?;
+    }
+
+    Ok(())
+}
+
+/// Writes a comment line listing the modules that failed the module test, if any.
+fn write_insignificant_modules(
+    f: &mut impl Write,
+    heading: &str,
+    insignificant_modules: &HashSet<String>,
+) -> std::io::Result<()> {
+    if insignificant_modules.is_empty() {
+        return Ok(());
+    }
+
+    let module_ids = insignificant_modules
+        .iter()
+        .sorted_by(|a, b| cmp_module_ids(a, b));
+
+    writeln!(f, "# {} {}", heading, module_ids.format(" "))
+}
+
+/// Writes the reference partition as an Infomap tree, `path flow name node`,
+/// followed by the node's significance on each level from the top as `1` or
+/// `0`, and the deepest level down to which the node is significant.
+///
+/// Ranks, flows and names come from the tree the partition was read from, if
+/// any, otherwise nodes are ranked in order with zero flow. The links of an
+/// `.ftree` are written back too.
+pub fn write_tree(
+    network: &Network,
+    significant_cores: &HashMap<String, HashSet<NodeId>>,
+    insignificant_modules: &HashSet<String>,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut nodes = node_paths(&network.modules, significant_cores, labels);
+
+    let leaf = |node: &NodeId| network.tree.as_ref().and_then(|tree| tree.leaves.get(node));
+
+    // Infomap trees list the nodes depth first
+    nodes.sort_by(|(node1, entries1), (node2, entries2)| {
+        let path1 = entries1.iter().map(|(module, _)| module.label.as_str());
+        let path2 = entries2.iter().map(|(module, _)| module.label.as_str());
+        let rank = |node| leaf(node).map_or("", |leaf| leaf.rank.as_str());
+
+        path1
+            .zip_longest(path2)
+            .map(|pair| match pair {
+                itertools::EitherOrBoth::Both(a, b) => cmp_labels(a, b),
+                itertools::EitherOrBoth::Left(_) => std::cmp::Ordering::Greater,
+                itertools::EitherOrBoth::Right(_) => std::cmp::Ordering::Less,
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| cmp_labels(rank(node1), rank(node2)))
+            .then_with(|| cmp_labels(labels.label(*node1), labels.label(*node2)))
+    });
+
+    if let Some(tree) = &network.tree {
+        for line in tree.header.iter() {
+            if !line.starts_with("# path") {
+                writeln!(f, "{}", line)?;
+            }
+        }
+    }
+
+    write_insignificant_modules(f, "insignificant modules", insignificant_modules)?;
+
+    writeln!(
+        f,
+        "# path flow name node_id significant deepest_significant_level"
+    )?;
+
+    let mut ranks = HashMap::new();
+
+    for (node, entries) in nodes.iter() {
+        let module_id = &entries.last().unwrap().0.module_id;
+
+        let (rank, flow, name) = match leaf(node) {
+            Some(leaf) => (leaf.rank.clone(), leaf.flow, leaf.name.as_str()),
+            None => {
+                let rank = ranks.entry(module_id).or_insert(0);
+                *rank += 1;
+                (rank.to_string(), 0.0, labels.label(*node))
+            }
+        };
+
+        let significant = entries
+            .iter()
+            .map(|&(_, significant)| if significant { '1' } else { '0' })
+            .collect::<String>();
+
+        let deepest = entries
+            .iter()
+            .take_while(|(_, significant)| *significant)
+            .count();
+
+        writeln!(
+            f,
+            "{}:{} {} \"{}\" {} {} {}",
+            module_id,
+            rank,
+            flow,
+            name,
+            labels.label(*node),
+            significant,
+            deepest
+        )?;
+    }
+
+    if let Some(links) = &network.links {
+        write_links(f, links)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    node: &'a str,
+    path: Vec<JsonModule<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonModule<'a> {
+    module: &'a str,
+    significant: bool,
+    score: f32,
+    /// Whether the module passed the module test.
+    module_significant: bool,
+}
+
+/// Writes the result as JSON, `{"nodes": [{"node", "path"}, ...]}`, or as
+/// JSON Lines with one node per line.
+///
+/// The path holds each module's label, significance and score, the fraction
+/// of matched bootstrap modules that contain the node.
+pub fn write_json(
+    modules: &HashMap<String, Module>,
+    significant_cores: &HashMap<String, HashSet<NodeId>>,
+    insignificant_modules: &HashSet<String>,
+    scores: &HashMap<String, HashMap<NodeId, f32>>,
+    labels: &Labels,
+    f: &mut impl Write,
+    json_lines: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let nodes = node_paths(modules, significant_cores, labels)
+        .into_iter()
+        .map(|(node, entries)| JsonNode {
+            node: labels.label(node),
+            path: entries
+                .into_iter()
+                .map(|(module, significant)| JsonModule {
+                    module: &module.label,
+                    significant,
+                    score: scores
+                        .get(&module.module_id)
+                        .and_then(|scores| scores.get(&node))
+                        .copied()
+                        .unwrap_or_default(),
+                    module_significant: !insignificant_modules.contains(&module.module_id),
+                })
+                .collect(),
+        });
+
+    if json_lines {
+        for node in nodes {
+            serde_json::to_writer(&mut *f, &node)?;
+            writeln!(f)?;
+        }
+    } else {
+        #[derive(Serialize)]
+        struct JsonOutput<'a> {
+            nodes: Vec<JsonNode<'a>>,
+        }
+
+        let output = JsonOutput {
+            nodes: nodes.collect(),
+        };
+
+        serde_json::to_writer(&mut *f, &output)?;
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the highest swept confidence at which each node is significant in
+/// each of its modules, or [`MISSING`] if it is never significant.
+///
+/// `sweep` holds the significant cores and the modules that failed the module
+/// test at each confidence, in increasing order.
+pub fn write_sweep(
+    modules: &HashMap<String, Module>,
+    sweep: &[(f32, SignificantCores, HashSet<String>)],
+    labels: &Labels,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (_, significant_cores, _) = match sweep.first() {
+        Some(first) => first,
+        None => return Err("No confidences to sweep".into()),
+    };
+
+    let nodes = node_paths(modules, significant_cores, labels);
+
+    for (conf, _, insignificant_modules) in sweep.iter() {
+        let heading = format!("insignificant modules at conf {}", conf);
+        write_insignificant_modules(f, &heading, insignificant_modules)?;
+    }
+
+    writeln!(f, "# path node conf...")?;
+
+    for (node, entries) in nodes.iter() {
+        let path = entries.iter().map(|(module, _)| &module.label).join(":");
+
+        let confs = entries.iter().map(|(module, _)| {
+            sweep
+                .iter()
+                .rev()
+                .find(|(_, cores, _)| cores[&module.module_id].contains(node))
+                .map_or_else(|| MISSING.to_owned(), |(conf, _, _)| conf.to_string())
+        });
+
+        writeln!(f, "{} {} {}", path, labels.label(*node), confs.format(" "))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the agreement on each level, the nodes whose significance changed,
+/// the modules whose core size changed and the modules whose significance
+/// changed between two results.
+pub fn write_diff(
+    diff: &crate::diff::Diff,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(
+        f,
+        "# level nodes significant_both first_only second_only agreement jaccard"
+    )?;
+
+    for (level, agreement) in diff.levels.iter() {
+        writeln!(
+            f,
+            "{} {} {} {} {} {:.4} {:.4}",
+            level,
+            agreement.nodes,
+            agreement.both,
+            agreement.first_only,
+            agreement.second_only,
+            agreement.fraction(),
+            agreement.jaccard()
+        )?;
+    }
+
+    writeln!(f, "# module node first second")?;
+
+    for change in diff.changes.iter() {
+        writeln!(
+            f,
+            "{} {} {} {}",
+            change.module_id,
+            labels.label(change.node),
+            change.first as u8,
+            !change.first as u8
+        )?;
+    }
+
+    writeln!(f, "# module core_size_first core_size_second")?;
+
+    for (module_id, first, second) in diff.core_sizes.iter() {
+        writeln!(f, "{} {} {}", module_id, first, second)?;
+    }
+
+    writeln!(f, "# module significant_first significant_second")?;
+
+    for (module_id, first) in diff.module_changes.iter() {
+        writeln!(f, "{} {} {}", module_id, *first as u8, !first as u8)?;
+    }
+
+    if !diff.unmatched.is_empty() {
+        writeln!(f, "# modules in only one result")?;
+
+        for module_id in diff.unmatched.iter() {
+            writeln!(f, "{}", module_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonTreeModule<'a> {
+    id: &'a str,
+    name: &'a str,
+    level: u8,
+    size: usize,
+    core_size: usize,
+    /// Whether the module passed the module test.
+    significant: bool,
+    flow: f64,
+    /// Mean score of the nodes in the module.
+    mean_score: f32,
+    /// Lowest score of the nodes in the core, or 0 if it is empty.
+    min_core_score: f32,
+    children: Vec<JsonTreeChild<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonTreeLeaf<'a> {
+    node: &'a str,
+    name: &'a str,
+    flow: f64,
+    /// Significance and score in the module the node is a leaf of.
+    significant: bool,
+    score: f32,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonTreeChild<'a> {
+    Module(JsonTreeModule<'a>),
+    Leaf(JsonTreeLeaf<'a>),
+}
+
+/// The module hierarchy of a network, for [`write_json_tree`].
+struct JsonTreeBuilder<'a> {
+    network: &'a Network,
+    significant_cores: &'a HashMap<String, HashSet<NodeId>>,
+    insignificant_modules: &'a HashSet<String>,
+    scores: &'a HashMap<String, HashMap<NodeId, f32>>,
+    labels: &'a Labels,
+    /// Submodules of each module, with `None` for the top level.
+    submodules: HashMap<Option<&'a str>, Vec<&'a Module>>,
+    /// Nodes that are leaves of each module.
+    leaves: HashMap<&'a str, Vec<NodeId>>,
+}
+
+impl<'a> JsonTreeBuilder<'a> {
+    fn flow(&self, node: &NodeId) -> f64 {
+        self.network
+            .tree
+            .as_ref()
+            .and_then(|tree| tree.leaves.get(node))
+            .map_or(0.0, |leaf| leaf.flow)
+    }
+
+    fn score(&self, module_id: &str, node: &NodeId) -> f32 {
+        self.scores
+            .get(module_id)
+            .and_then(|scores| scores.get(node))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn module(&self, module: &'a Module) -> JsonTreeModule<'a> {
+        let module_id = module.module_id.as_str();
+        let core = self.significant_cores.get(module_id);
+
+        let mean_score = match module.nodes.len() {
+            0 => 0.0,
+            len => {
+                let sum = module.nodes.iter().map(|node| self.score(module_id, node));
+                sum.sum::<f32>() / len as f32
+            }
+        };
+
+        let min_core_score = core
+            .and_then(|core| {
+                core.iter()
+                    .map(|node| self.score(module_id, node))
+                    .reduce(f32::min)
+            })
+            .unwrap_or_default();
+
+        JsonTreeModule {
+            id: module_id,
+            name: &module.label,
+            level: module.level,
+            size: module.nodes.len(),
+            core_size: core.map_or(0, |core| core.len()),
+            significant: !self.insignificant_modules.contains(module_id),
+            flow: module.nodes.iter().map(|node| self.flow(node)).sum(),
+            mean_score,
+            min_core_score,
+            children: self.children(Some(module_id)),
+        }
+    }
+
+    fn leaf(&self, module_id: &str, node: &NodeId) -> JsonTreeLeaf<'a> {
+        let label = self.labels.label(*node);
+        let leaf = self
+            .network
+            .tree
+            .as_ref()
+            .and_then(|tree| tree.leaves.get(node));
+
+        JsonTreeLeaf {
+            node: label,
+            name: leaf.map_or(label, |leaf| &leaf.name),
+            flow: self.flow(node),
+            significant: self
+                .significant_cores
+                .get(module_id)
+                .is_some_and(|core| core.contains(node)),
+            score: self.score(module_id, node),
+        }
+    }
+
+    /// Submodules sorted by label, followed by the leaf nodes.
+    fn children(&self, parent: Option<&str>) -> Vec<JsonTreeChild<'a>> {
+        let mut modules = self.submodules.get(&parent).cloned().unwrap_or_default();
+        modules.sort_by(|a, b| cmp_labels(&a.label, &b.label));
+
+        let mut children = modules
+            .into_iter()
+            .map(|module| JsonTreeChild::Module(self.module(module)))
+            .collect::<Vec<_>>();
+
+        if let Some(parent) = parent {
+            if let Some(nodes) = self.leaves.get(parent) {
+                children.extend(
+                    nodes
+                        .iter()
+                        .map(|node| JsonTreeChild::Leaf(self.leaf(parent, node))),
+                );
+            }
+        }
+
+        children
+    }
+}
+
+/// Writes the reference partition as nested JSON, `{"children": [...]}`,
+/// where each module has its submodules and then its leaf nodes as children.
+///
+/// Modules hold their size, core size, total flow of their nodes and stats
+/// of the node scores, the fraction of matched bootstrap modules that contain
+/// the node. Flows are 0 unless read from a tree.
+pub fn write_json_tree(
+    network: &Network,
+    significant_cores: &HashMap<String, HashSet<NodeId>>,
+    insignificant_modules: &HashSet<String>,
+    scores: &HashMap<String, HashMap<NodeId, f32>>,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut submodules = HashMap::new();
+    let mut leaves = HashMap::new();
+
+    for module in network.modules.values() {
+        submodules
+            .entry(module.parent_id())
+            .or_insert_with(Vec::new)
+            .push(module);
+    }
+
+    // Nodes are sorted by label within each module
+    for (node, entries) in node_paths(&network.modules, significant_cores, labels) {
+        let (module, _) = entries.last().unwrap();
+        leaves
+            .entry(module.module_id.as_str())
+            .or_insert_with(Vec::new)
+            .push(node);
+    }
+
+    let builder = JsonTreeBuilder {
+        network,
+        significant_cores,
+        insignificant_modules,
+        scores,
+        labels,
+        submodules,
+        leaves,
+    };
+
+    #[derive(Serialize)]
+    struct JsonTree<'a> {
+        children: Vec<JsonTreeChild<'a>>,
+    }
+
+    let tree = JsonTree {
+        children: builder.children(None),
+    };
+    serde_json::to_writer(&mut *f, &tree)?;
+    writeln!(f)?;
+
+    Ok(())
+}
+
+/// Writes the similarity of each bootstrap partition to the reference on each level.
+pub fn write_similarity(
+    similarities: &BTreeMap<NetworkId, BTreeMap<u8, crate::similarity::PartitionSimilarity>>,
+    f: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(f, "# partition level nmi ari weighted_jaccard")?;
+
+    for (network_id, levels) in similarities.iter() {
+        for (level, similarity) in levels.iter() {
+            writeln!(
+                f,
+                "{} {} {:.6} {:.6} {:.6}",
+                network_id, level, similarity.nmi, similarity.ari, similarity.weighted_jaccard
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_frequencies(
+    frequencies: &HashMap<String, HashMap<NodeId, f32>>,
+    insignificant_modules: &HashSet<String>,
+    labels: &Labels,
+    f: &mut impl Write,
+) -> std::io::Result<()> {
+    write_insignificant_modules(f, "insignificant modules", insignificant_modules)?;
+    writeln!(f, "# module node frequency")?;
+
+    for (module_id, nodes) in frequencies
+        .iter()
+        .sorted_by_key(|(module_id, _)| *module_id)
+    {
+        let nodes = nodes
+            .iter()
+            .map(|(&node, frequency)| (labels.label(node), frequency))
+            .sorted_by(|(node1, _), (node2, _)| cmp_labels(node1, node2));
+
+        for (node, frequency) in nodes {
+            writeln!(f, "{} {} {}", module_id, node, frequency)?;
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for this test process, removed when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!(
+                "significance_clustering_{}_{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_read_input_labels() {
+        let input = "# node partitions\nQ42 a:b x\nQ7 a:c x\n";
+        let (networks, labels) = read_input(input).unwrap();
+
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.label(labels.get("Q7").unwrap()), "Q7");
+
+        let first = &networks[&0];
+        assert_eq!(first.modules.len(), 3);
+        assert_eq!(first.modules["a"].nodes.len(), 2);
+        assert_eq!(first.modules["a:b"].label, "b");
+        assert_eq!(first.modules["a:b"].level, 2);
+        assert!(first.modules["a:c"]
+            .nodes
+            .contains(&labels.get("Q7").unwrap()));
+    }
+
+    #[test]
+    fn test_read_input_missing() {
+        let input = "1 1 1 -\n2 1 - 2\n3 2 2 2\n";
+        let (networks, _) = read_input(input).unwrap();
+
+        assert_eq!(networks[&0].nodes.len(), 3);
+        assert_eq!(networks[&1].nodes.len(), 2);
+        assert_eq!(networks[&2].nodes.len(), 2);
+        assert_eq!(networks[&2].modules["2"].nodes.len(), 2);
+
+        assert!(read_input("1 1 1 1\n2 1 1\n").is_err());
+    }
+
+    #[test]
+    fn test_read_tree() {
+        let input = "# v1\n1:1 0.5 \"a b\" 10\n1:2 0.25 \"c\" 20\n2:1:1 0.25 \"d\" 30\n";
+        let mut labels = Labels::new();
+        let network = read_tree(input, &mut labels).unwrap();
+
+        assert_eq!(network.nodes.len(), 3);
+        assert_eq!(network.modules["1"].nodes.len(), 2);
+        assert_eq!(network.modules["2:1"].nodes.len(), 1);
+        assert!(network.modules["2:1"]
+            .nodes
+            .contains(&labels.get("30").unwrap()));
+    }
+
+    #[test]
+    fn test_read_ftree() {
+        let input = "# path flow name node_id\n\
+            1:1 0.5 \"a\" 1\n\
+            1:2 0.25 \"b\" 2\n\
+            2:1 0.25 \"c\" 3\n\
+            *Links undirected\n\
+            #*Links path enterFlow exitFlow numEdges numChildren\n\
+            *Links root 0 0 1 2\n\
+            1 2 0.1\n\
+            *Links 1 0.1 0.1 1 2\n\
+            1 2 0.2\n";
+
+        assert_eq!(Format::detect("input.txt", input), Format::Ftree);
+
+        let mut labels = Labels::new();
+        let network = read_partition(input, Format::Ftree, &mut labels).unwrap();
+        assert_eq!(network.modules["1"].nodes.len(), 2);
+
+        let leaf = &network.tree.as_ref().unwrap().leaves[&labels.get("2").unwrap()];
+        assert_eq!((leaf.rank.as_str(), leaf.flow), ("2", 0.25));
+
+        let links = network.links.unwrap();
+        assert_eq!(links.link_type, "undirected");
+        assert_eq!(links.modules.len(), 2);
+        assert_eq!(links.modules[1].module_id, "1");
+        assert_eq!(links.modules[1].links, vec![(1, 2, 0.2)]);
+
+        let mut out = Vec::new();
+        write_links(&mut out, &links).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("*Links 1 0.1 0.1 1 2\n1 2 0.2\n"));
+    }
+
+    #[test]
+    fn test_read_clu() {
+        let input = "# module level 1\n# node_id module flow\n1 1 0.5\n2 1 0.25\na 2 0.25\n";
+
+        assert_eq!(Format::detect("input.txt", input), Format::Clu);
+        assert_eq!(Format::detect("input.clu", ""), Format::Clu);
+        assert_eq!(Format::detect("input.txt", "1 1 1\n"), Format::Columns);
+
+        let mut labels = Labels::new();
+        let network = read_partition(input, Format::Clu, &mut labels).unwrap();
+        assert_eq!(network.modules.len(), 2);
+        assert_eq!(network.modules["1"].nodes.len(), 2);
+        assert!(network.modules["2"]
+            .nodes
+            .contains(&labels.get("a").unwrap()));
+    }
+
+    #[test]
+    fn test_read_json() {
+        let input = r#"{
+            "reference": {"a": [1, 1], "b": [1, "x"], "c": [2]},
+            "bootstraps": [{"a": [1], "b": [1], "c": [2]}, {"a": [1], "b": null}]
+        }"#;
+
+        assert_eq!(Format::detect("input.txt", input), Format::Json);
+
+        let (networks, labels) = read_json(input).unwrap();
+        assert_eq!(labels.len(), 3);
+        assert_eq!(networks.len(), 3);
+        assert_eq!(networks[&0].modules["1:x"].label, "x");
+        assert_eq!(networks[&2].nodes.len(), 1);
+
+        let mut labels = Labels::new();
+        let network = read_partition(r#"{"a": ["b:c"]}"#, Format::Json, &mut labels);
+        assert!(network.is_err());
+    }
+
+    #[test]
+    fn test_read_columns_from() {
+        let input = "# node partitions\n1 1:1 1\n2 1:2:1 2\n";
+        let mut labels = Labels::new();
+        let networks = read_columns_from(input.as_bytes(), &mut labels).unwrap();
+
+        let first = &networks[&0];
+        assert_eq!(first.modules.len(), 4);
+        assert_eq!(first.modules["1"].nodes.len(), 2);
+        assert_eq!(first.modules["1:2:1"].level, 3);
+        assert_eq!(networks[&1].modules.len(), 2);
+    }
+
+    #[test]
+    fn test_open_compressed() {
+        let input = "1 1 1\n2 1 2\n";
+        let dir = TempDir::new("open_compressed");
+        let dir = &dir.0;
+
+        let gzip_file = dir.join("significance_clustering_test.txt.gz");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&gzip_file).unwrap(), Default::default());
+        encoder.write_all(input.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let zstd_file = dir.join("significance_clustering_test.txt.zst");
+        std::fs::write(&zstd_file, zstd::encode_all(input.as_bytes(), 0).unwrap()).unwrap();
+
+        for file in [gzip_file, zstd_file] {
+            let file_name = file.to_str().unwrap();
+            let mut reader = open(file_name).unwrap();
+            assert_eq!(
+                detect_format(file_name, &mut reader).unwrap(),
+                Format::Columns
+            );
+
+            let networks = read_columns_from(reader, &mut Labels::new()).unwrap();
+            assert_eq!(networks[&1].modules.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_join() {
+        let mut labels = Labels::new();
+        let reference = read_partition("a 1\nb 1\nc 2\n", Format::Columns, &mut labels).unwrap();
+        let mut network = read_partition("a 1\nb 2\nd 2\n", Format::Columns, &mut labels).unwrap();
+
+        let mismatch = join(&reference, &mut network);
+        assert_eq!(mismatch.missing, vec![labels.get("c").unwrap()]);
+        assert_eq!(mismatch.unknown, vec![labels.get("d").unwrap()]);
+
+        assert_eq!(network.nodes.len(), 2);
+        assert_eq!(network.modules["2"].nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_find_files() {
+        let dir = TempDir::new("find_files");
+        let dir = &dir.0;
+
+        for file in ["b.tree", "a.tree", "c.clu"] {
+            std::fs::write(dir.join(file), "1:1 1 \"a\" 1\n").unwrap();
+        }
+
+        let files = find_files(dir.to_str().unwrap()).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(files[0].ends_with("a.tree"));
+
+        let pattern = dir.join("*.tree");
+        let files = find_files(pattern.to_str().unwrap()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[1].ends_with("b.tree"));
+    }
+
+    #[test]
+    fn test_read_result_file() {
+        let dir = TempDir::new("read_result_file");
+        let text = dir.0.join("result.txt");
+        let tree = dir.0.join("result.tree");
+        std::fs::write(&text, "# insignificant modules 1\n1:1 a\n1;1 b\n").unwrap();
+        std::fs::write(
+            &tree,
+            "# path flow name node_id significant\n1:1 0 \"a\" a 1\n",
+        )
+        .unwrap();
+
+        let mut labels = Labels::new();
+        let (network, cores, insignificant_modules) =
+            read_result_file(text.to_str().unwrap(), &mut labels).unwrap();
+        assert_eq!(network.modules.len(), 2);
+        assert_eq!(cores["1"].len(), 1);
+        assert!(insignificant_modules.contains("1"));
+
+        let err = read_result_file(tree.to_str().unwrap(), &mut labels).unwrap_err();
+        assert!(err.to_string().contains("only text results"));
+    }
+
+    #[test]
+    fn test_write_sweep() {
+        let (networks, labels) = read_input("a 1:1\nb 1:1\nc 1:2\n").unwrap();
+        let modules = &networks[&0].modules;
+
+        let cores = |nodes: &[&[&str]]| {
+            ["1", "1:1", "1:2"]
+                .iter()
+                .zip(nodes)
+                .map(|(module_id, core)| {
+                    let core = core
+                        .iter()
+                        .map(|&label| labels.get(label).unwrap())
+                        .collect();
+                    (module_id.to_string(), core)
+                })
+                .collect::<HashMap<_, HashSet<_>>>()
+        };
+
+        let sweep = [
+            (
+                0.5,
+                cores(&[&["a", "b", "c"], &["a", "b"], &["c"]]),
+                HashSet::new(),
+            ),
+            (
+                0.9,
+                cores(&[&["a", "b"], &["a"], &[]]),
+                HashSet::from(["1:2".to_owned()]),
+            ),
+        ];
+
+        let mut output = Vec::new();
+        write_sweep(modules, &sweep, &labels, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# insignificant modules at conf 0.9 1:2\n\
+             # path node conf...\n1:1 a 0.9 0.9\n1:1 b 0.9 0.5\n1:2 c 0.5 0.5\n"
+        );
+    }
+
+    #[test]
+    fn test_write_similarity() {
+        let (mut networks, _) = read_input("a 1 1\nb 1 1\nc 2 1\n").unwrap();
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = crate::similarity::get_most_similar_modules(&first, &networks);
+        let similarities =
+            crate::similarity::get_partition_similarities(&first, &networks, &most_similar_modules);
+
+        let mut output = Vec::new();
+        write_similarity(&similarities, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# partition level nmi ari weighted_jaccard\n1 1 0.000000 0.000000 0.555556\n"
+        );
+    }
+
+    #[test]
+    fn test_read_result_and_diff() {
+        let mut labels = Labels::new();
+        let (network, first, first_insignificant) = read_result(
+            "# insignificant modules 1:2\n1:1 a\n1:1; b\n1;2; c\n",
+            &mut labels,
+        )
+        .unwrap();
+        let (_, second, second_insignificant) =
+            read_result("1:1 a\n1:1 b\n1;2; c\n", &mut labels).unwrap();
+
+        assert_eq!(network.modules.len(), 3);
+        assert_eq!(first_insignificant, HashSet::from(["1:2".to_owned()]));
+        assert!(second_insignificant.is_empty());
+        assert_eq!(first["1"].len(), 2);
+        assert_eq!(first["1:1"].len(), 1);
+        assert!(first["1:2"].is_empty());
+        assert!(read_result("1:;2 a\n", &mut labels).is_err());
+        assert!(read_result("# path node 0.9\n1:1 a 0.9\n", &mut labels).is_err());
+        assert!(read_result("# module node frequency\n1 a 1\n", &mut labels).is_err());
+
+        let mut output = Vec::new();
+        write_result(
+            &network.modules,
+            &first,
+            &first_insignificant,
+            &labels,
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let (_, cores, insignificant_modules) = read_result(&output, &mut labels).unwrap();
+        assert_eq!(cores, first);
+        assert_eq!(insignificant_modules, first_insignificant);
+
+        let diff = crate::diff::diff(
+            &network.modules,
+            &first,
+            &second,
+            &first_insignificant,
+            &second_insignificant,
+        );
+        let mut output = Vec::new();
+        write_diff(&diff, &labels, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# level nodes significant_both first_only second_only agreement jaccard\n\
+             1 3 2 0 0 1.0000 1.0000\n\
+             2 3 1 0 1 0.6667 0.5000\n\
+             # module node first second\n\
+             1:1 b 0 1\n\
+             # module core_size_first core_size_second\n\
+             1:1 1 2\n\
+             # module significant_first significant_second\n\
+             1:2 0 1\n"
+        );
+    }
+
+    #[test]
+    fn test_write_frequencies() {
+        let mut labels = Labels::new();
+        let (a, b) = (labels.intern("a"), labels.intern("b"));
+
+        let frequencies = HashMap::from([
+            ("1".to_owned(), HashMap::from([(b, 0.5), (a, 1.0)])),
+            ("2".to_owned(), HashMap::new()),
+        ]);
+        let insignificant_modules = HashSet::from(["2".to_owned()]);
+
+        let mut output = Vec::new();
+        write_frequencies(&frequencies, &insignificant_modules, &labels, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# insignificant modules 2\n\
+             # module node frequency\n\
+             1 a 1\n\
+             1 b 0.5\n"
+        );
+    }
+
+    #[test]
+    fn test_write_tree() {
+        let input = "# v1\n# path flow name node_id\n\
+            1:1:2 0.25 \"a b\" 10\n\
+            1:2:1 0.25 \"c\" 20\n\
+            1:1:1 0.25 \"d\" 30\n\
+            2:1 0.25 \"e\" 40\n";
+        let mut labels = Labels::new();
+        let network = read_tree(input, &mut labels).unwrap();
+        let node = |label| labels.get(label).unwrap();
+
+        let mut cores = network
+            .modules
+            .iter()
+            .map(|(module_id, module)| (module_id.clone(), module.nodes.clone()))
+            .collect::<HashMap<_, _>>();
+        cores.get_mut("1:1").unwrap().remove(&node("10"));
+        cores.get_mut("1").unwrap().remove(&node("20"));
+
+        let mut output = Vec::new();
+        let insignificant_modules = HashSet::from(["1:2".to_owned(), "1".to_owned()]);
+        write_tree(
+            &network,
+            &cores,
+            &insignificant_modules,
+            &labels,
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# v1\n\
+            # insignificant modules 1 1:2\n\
+            # path flow name node_id significant deepest_significant_level\n\
+            1:1:1 0.25 \"d\" 30 11 2\n\
+            1:1:2 0.25 \"a b\" 10 10 1\n\
+            1:2:1 0.25 \"c\" 20 01 0\n\
+            2:1 0.25 \"e\" 40 1 1\n"
+        );
+
+        // The output is still a valid tree, with the same nodes
+        let again = read_tree(&output, &mut labels).unwrap();
+        assert_eq!(again.nodes, network.nodes);
+        for (module_id, module) in network.modules.iter() {
+            assert_eq!(again.modules[module_id].nodes, module.nodes);
+        }
+        assert_eq!(
+            again.tree.unwrap().leaves[&labels.get("10").unwrap()].name,
+            "a b"
+        );
+    }
+
+    #[test]
+    fn test_write_json_tree() {
+        let input =
+            "1:1:1 0.25 \"a\" 10\n1:1:2 0.25 \"b\" 20\n1:2 0.25 \"c\" 30\n2:1 0.25 \"d\" 40\n";
+        let mut labels = Labels::new();
+        let network = read_tree(input, &mut labels).unwrap();
+        let node = |label| labels.get(label).unwrap();
+
+        let mut cores = network
+            .modules
+            .iter()
+            .map(|(module_id, module)| (module_id.clone(), module.nodes.clone()))
+            .collect::<HashMap<_, _>>();
+        cores.get_mut("1:1").unwrap().remove(&node("20"));
+
+        let scores = cores
+            .iter()
+            .map(|(module_id, core)| {
+                let scores = core.iter().map(|&node| (node, 1.0)).collect();
+                (module_id.clone(), scores)
+            })
+            .collect();
+
+        let mut output = Vec::new();
+        let insignificant_modules = HashSet::from(["2".to_owned()]);
+        write_json_tree(
+            &network,
+            &cores,
+            &insignificant_modules,
+            &scores,
+            &labels,
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let tree: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        let top = tree["children"].as_array().unwrap();
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0]["id"], "1");
+        assert_eq!(top[0]["size"], 3);
+        assert_eq!(top[0]["flow"], 0.75);
+        assert_eq!(top[0]["significant"], true);
+        assert_eq!(top[1]["significant"], false);
+
+        // Submodule 1:1 comes before the leaf node c
+        let children = top[0]["children"].as_array().unwrap();
+        assert_eq!(children[0]["id"], "1:1");
+        assert_eq!(children[0]["core_size"], 1);
+        assert_eq!(children[0]["mean_score"], 0.5);
+        assert_eq!(children[1]["node"], "30");
+        assert_eq!(children[1]["name"], "c");
+        assert_eq!(children[1]["significant"], true);
+
+        let leaves = children[0]["children"].as_array().unwrap();
+        assert_eq!(leaves[1]["node"], "20");
+        assert_eq!(leaves[1]["significant"], false);
+        assert_eq!(leaves[1]["score"], 0.0);
+    }
+
+    #[test]
+    fn test_cmp_labels() {
+        let mut labels = vec!["b", "10", "2", "a"];
+        labels.sort_by(|a, b| cmp_labels(a, b));
+        assert_eq!(labels, vec!["2", "10", "a", "b"]);
+    }
+}
@@ -1,10 +1,7 @@
-#![feature(test)]
-#![feature(map_first_last)]
-#![feature(bool_to_option)]
-
 #[cfg(not(target_arch = "wasm32"))]
 mod target_arch {
     pub use rayon::prelude::*;
+    pub use std::collections::BTreeMap;
     pub use std::io::Write;
     pub use std::sync::atomic::AtomicUsize;
     pub use std::sync::atomic::Ordering;
@@ -23,12 +20,19 @@ use target_arch::*;
 
 use hashbrown::{HashMap, HashSet};
 
-pub use config::Config;
+pub use config::{Confidence, Config, Consistency, Optimizer, OutputFormat};
 
+pub mod bootstrap;
+pub mod cache;
 pub mod clustering;
 pub mod config;
+pub mod diff;
 pub mod io;
+pub mod optimizer;
+pub mod partitioner;
 pub mod similarity;
+pub mod synthetic;
+pub mod validation;
 
 pub type NodeId = u32;
 pub type NetworkId = usize;
@@ -36,53 +40,91 @@ pub type NetworkId = usize;
 #[derive(Debug, Clone)]
 pub struct Module {
     pub module_id: String,
-    pub module: u32,
+    pub label: String,
     pub level: u8,
     pub nodes: HashSet<NodeId>,
 }
 
 impl Module {
+    #[allow(clippy::double_ended_iterator_last)]
     fn new(id: &str) -> Module {
         let path = id.split(':');
         let level = path.clone().count().try_into().unwrap();
         Module {
             module_id: id.to_owned(),
-            module: path.last().and_then(|s| s.parse().ok()).unwrap(),
+            label: path.last().unwrap().to_owned(),
             level,
             nodes: HashSet::new(),
         }
     }
+
+    /// The id of the module one level up, if any.
+    pub fn parent_id(&self) -> Option<&str> {
+        self.module_id.rsplit_once(':').map(|(parent, _)| parent)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Network {
     pub modules: HashMap<String, Module>,
+    /// All nodes present in the partition.
+    pub nodes: HashSet<NodeId>,
+    /// Nodes read more than once, such as from duplicate rows, which are merged.
+    pub duplicates: HashSet<NodeId>,
+    /// Links between modules, if read from an `.ftree` file.
+    pub links: Option<io::Links>,
+    /// Ranks, flows and names of the nodes, if read from a `.tree` file.
+    pub tree: Option<io::Tree>,
 }
 
 impl Network {
     fn new() -> Network {
         Network {
             modules: HashMap::new(),
+            nodes: HashSet::new(),
+            duplicates: HashSet::new(),
+            links: None,
+            tree: None,
         }
     }
 
+    /// Removes all nodes not in `nodes`, and modules that become empty.
+    pub fn retain_nodes(&mut self, nodes: &HashSet<NodeId>) {
+        self.nodes.retain(|node| nodes.contains(node));
+        self.duplicates.retain(|node| nodes.contains(node));
+
+        for module in self.modules.values_mut() {
+            module.nodes.retain(|node| nodes.contains(node));
+        }
+
+        self.modules.retain(|_, module| !module.nodes.is_empty());
+    }
+
     fn add_node(&mut self, module_id: &str, node_id: NodeId) {
-        self.modules
-            .entry(module_id.to_string())
-            .or_insert_with(|| Module::new(module_id))
-            .nodes
-            .insert(node_id);
+        self.nodes.insert(node_id);
+
+        // Only allocate the module id for new modules
+        match self.modules.get_mut(module_id) {
+            Some(module) => {
+                module.nodes.insert(node_id);
+            }
+            None => {
+                let mut module = Module::new(module_id);
+                module.nodes.insert(node_id);
+                self.modules.insert(module_id.to_owned(), module);
+            }
+        }
     }
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn run(contents: &str, conf: f32, seed: u32) -> Map {
-    let (first, rest) = {
-        let mut networks = io::read_input(contents).unwrap();
+    let (first, rest, labels) = {
+        let (mut networks, labels) = io::read_input(contents).unwrap();
         let first = networks.remove(&0).unwrap();
         let rest = networks;
-        (first, rest)
+        (first, rest, labels)
     };
 
     let most_similar_modules = similarity::get_most_similar_modules(&first, &rest);
@@ -113,10 +155,16 @@ pub fn run(contents: &str, conf: f32, seed: u32) -> Map {
             nodes
                 .entry(node)
                 .or_insert_with(BTreeMap::new)
-                .insert(module.level, (module.module, significant));
+                .insert(module.level, (module.label.as_str(), significant));
         }
     }
 
+    // Numeric labels are passed as numbers, as before labels were supported
+    let to_js = |label: &str| match label.parse::<u32>() {
+        Ok(id) => JsValue::from(id),
+        Err(_) => JsValue::from(label),
+    };
+
     let result = Map::new();
 
     for (node, modules) in nodes.iter() {
@@ -124,12 +172,12 @@ pub fn run(contents: &str, conf: f32, seed: u32) -> Map {
 
         for &(module, significant) in modules.values() {
             let entry = Array::new();
-            entry.push(&JsValue::from(module));
+            entry.push(&to_js(module));
             entry.push(&JsValue::from(significant));
             array.push(&entry);
         }
 
-        result.set(&JsValue::from(*node), &array);
+        result.set(&to_js(labels.label(*node)), &array);
     }
 
     result
@@ -141,97 +189,592 @@ pub fn run(
         in_file,
         conf,
         seed,
+        restarts,
+        optimizer,
+        consistency,
+        missing,
         out_file,
+        out_format,
+        frequencies_file,
+        bootstraps,
+        cache_file,
+        sweep,
+        strict,
+        replicates_dir,
+        replicates,
+        resampling,
+        from_network,
+        diff_file,
+        similarity_file,
+        module_test,
         ..
     }: Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Multi-level significance clustering");
-    println!("Running with:");
-    println!("\t- conf: {}", conf);
-    println!("\t- seed: {}", seed);
-    println!("\t- output: {}", out_file);
-
-    print!("\nReading input file... ");
-    let (first, rest) = {
-        let mut networks = io::read_input(&in_file)?;
-        let first = networks.remove(&0).unwrap();
+    use itertools::Itertools;
+
+    eprintln!("Multi-level significance clustering");
+    eprintln!("Running with:");
+    if sweep.is_empty() {
+        eprintln!("\t- conf: {}", conf);
+    } else {
+        eprintln!("\t- sweep: {}", sweep.iter().join(","));
+    }
+    eprintln!("\t- seed: {}", seed);
+    eprintln!("\t- restarts: {}", restarts);
+    eprintln!("\t- optimizer: {:?}", optimizer);
+    eprintln!("\t- consistency: {:?}", consistency);
+    eprintln!("\t- missing: {:?}", missing);
+    eprintln!("\t- module test: {:?}", module_test);
+    eprintln!("\t- output: {}", out_file);
+
+    if let Some(replicates_dir) = replicates_dir {
+        eprint!("\nWriting {} bootstrap replicates... ", replicates);
+        bootstrap::write_replicates(&in_file, &replicates_dir, resampling, replicates, seed)?;
+        eprintln!("done");
+        return Ok(());
+    }
+
+    if let Some(diff_file) = diff_file {
+        eprint!("\nComparing results... ");
+        let mut labels = io::Labels::new();
+        let (first, first_cores, first_insignificant) =
+            io::read_result_file(&in_file, &mut labels)?;
+        let (_, second_cores, second_insignificant) =
+            io::read_result_file(&diff_file, &mut labels)?;
+        let diff = diff::diff(
+            &first.modules,
+            &first_cores,
+            &second_cores,
+            &first_insignificant,
+            &second_insignificant,
+        );
+        eprintln!("done ({} changed nodes)", diff.changes.len());
+
+        eprint!("Writing output... ");
+        io::write_diff(&diff, &labels, &mut io::create(&out_file)?)?;
+        eprintln!("done");
+        return Ok(());
+    }
+
+    eprint!("\nReading input file... ");
+    let (first, rest, labels) = {
+        let (mut networks, labels) = if cache::is_cache_file(&in_file) {
+            cache::load(&in_file)?
+        } else if from_network {
+            let (links, labels) = bootstrap::read_links_file(&in_file)?;
+            let partitioner = partitioner::Louvain::default();
+            let networks =
+                bootstrap::partition_replicates(&links, resampling, replicates, seed, &partitioner);
+            (networks, labels)
+        } else if bootstraps.is_empty() {
+            let mut reader = io::open(&in_file)?;
+
+            match io::detect_format(&in_file, &mut reader)? {
+                io::Format::Json => io::read_json_from(reader)?,
+                io::Format::Columns => {
+                    let mut labels = io::Labels::new();
+                    let networks = io::read_columns_from(reader, &mut labels)?;
+                    (networks, labels)
+                }
+                format => {
+                    return Err(format!(
+                        "{}: a single {:?} partition has no bootstrap partitions, \
+                         pass them with --bootstraps",
+                        in_file, format
+                    )
+                    .into())
+                }
+            }
+        } else {
+            read_bootstrap_files(&in_file, &bootstraps)?
+        };
+
+        if let Some(cache_file) = cache_file {
+            eprint!("done\nWriting cache file... ");
+            cache::write_cache(&networks, &labels, &mut io::create(&cache_file)?)?;
+            eprintln!("done");
+            return Ok(());
+        }
+
+        let first = networks
+            .remove(&0)
+            .ok_or("Empty input, expected a reference partition")?;
         let rest = networks;
-        (first, rest)
+        (first, rest, labels)
     };
 
     let num_nodes = first.modules.values().fold(0, |acc, m| acc + m.nodes.len());
-    println!(
+    eprintln!(
         "done ({} nodes in {} modules)",
         num_nodes,
         first.modules.len()
     );
 
-    print!("Computing similarities... ");
+    let thresholds = validation::Thresholds::default();
+
+    eprint!("Validating partitions... ");
+    let issues = validation::validate(&first, &rest, &labels, &thresholds);
+    report_issues(&issues, strict)?;
+
+    eprint!("Computing similarities... ");
     let start = Instant::now();
     let most_similar_modules = similarity::get_most_similar_modules(&first, &rest);
-    println!("done ({} ms)", start.elapsed().as_millis());
+    eprintln!("done ({} ms)", start.elapsed().as_millis());
 
-    let num_modules = most_similar_modules.len();
-    print!("Clustering... 0/{} done", num_modules);
-    std::io::stdout().flush().unwrap();
-    let start = Instant::now();
-    let current_count = Arc::new(AtomicUsize::new(0));
+    eprint!("Validating matches... ");
+    let issues = validation::validate_matches(&first, &rest, &most_similar_modules, &thresholds);
+    report_issues(&issues, strict)?;
 
-    let significant_cores = most_similar_modules
-        .par_iter()
-        .map(|(module_id1, networks)| {
-            let module = &first.modules[module_id1].nodes;
+    if let Some(similarity_file) = similarity_file {
+        eprint!("Comparing partitions... ");
+        let partition_similarities =
+            similarity::get_partition_similarities(&first, &rest, &most_similar_modules);
+        eprintln!("done");
+        similarity::report(&partition_similarities);
 
-            let modules = networks
-                .iter()
-                .map(|(network_id, module_id)| &rest[network_id].modules[module_id].nodes)
-                .collect::<Vec<_>>();
+        io::write_similarity(&partition_similarities, &mut io::create(&similarity_file)?)?;
+    }
 
-            let core = clustering::get_significant_core(module, &modules, conf, seed);
+    // The exact optimizer tries every combination of matched modules to exclude
+    if optimizer == Optimizer::Exact {
+        for (module_id, matches) in most_similar_modules.iter() {
+            let level = first.modules[module_id].level;
+            let confs = if sweep.is_empty() {
+                vec![conf.for_level(level)]
+            } else {
+                sweep.clone()
+            };
+
+            for conf in confs {
+                let combinations = clustering::num_exact_combinations(matches.len(), conf);
+
+                if combinations > clustering::MAX_EXACT_COMBINATIONS {
+                    return Err(format!(
+                        "--optimizer exact would try {} combinations for module {} at conf {}, \
+                         at most {} are allowed",
+                        combinations,
+                        module_id,
+                        conf,
+                        clustering::MAX_EXACT_COMBINATIONS
+                    )
+                    .into());
+                }
+            }
+        }
+    }
 
-            let count = current_count.fetch_add(1, Ordering::SeqCst);
-            print!("\rClustering... {}/{} done", count, num_modules);
-            std::io::stdout().flush().unwrap();
+    let optimizer = optimizer.build(restarts);
 
-            (module_id1.to_string(), core)
-        })
-        .collect::<HashMap<String, HashSet<NodeId>>>();
+    let clustering = Clustering {
+        first: &first,
+        rest: &rest,
+        most_similar_modules: &most_similar_modules,
+        optimizer: optimizer.as_ref(),
+        seed,
+        consistency,
+        missing,
+        module_test,
+    };
 
-    println!(
-        "\rClustering... {}/{} done ({} ms)",
-        num_modules,
-        num_modules,
-        start.elapsed().as_millis()
-    );
+    if !sweep.is_empty() {
+        let mut results: Vec<(f32, io::SignificantCores, HashSet<String>)> = Vec::new();
+
+        // Cores shrink with the confidence, so warm start from the previous one
+        for &conf in sweep.iter() {
+            eprintln!("Confidence {}", conf);
+            let initial = results.last().map(|(_, cores, _)| cores);
+            let cores = clustering.cluster(&Confidence::new(conf), initial);
+            results.push((conf, cores.significant_cores, cores.insignificant_modules));
+        }
 
-    print!("Writing output file... ");
-    io::write_result(&first.modules, &significant_cores, &out_file)?;
-    println!("done");
+        eprint!("Writing output file... ");
+        io::write_sweep(
+            &first.modules,
+            &results,
+            &labels,
+            &mut io::create(&out_file)?,
+        )?;
+        eprintln!("done");
+
+        return Ok(());
+    }
+
+    let Cores {
+        significant_cores,
+        frequencies,
+        scores,
+        insignificant_modules,
+    } = clustering.cluster(&conf, None);
+
+    eprint!("Writing output file... ");
+    match out_format {
+        OutputFormat::Text => io::write_result(
+            &first.modules,
+            &significant_cores,
+            &insignificant_modules,
+            &labels,
+            &mut io::create(&out_file)?,
+        )?,
+        OutputFormat::Tree => io::write_tree(
+            &first,
+            &significant_cores,
+            &insignificant_modules,
+            &labels,
+            &mut io::create(&out_file)?,
+        )?,
+        OutputFormat::JsonTree => io::write_json_tree(
+            &first,
+            &significant_cores,
+            &insignificant_modules,
+            &scores,
+            &labels,
+            &mut io::create(&out_file)?,
+        )?,
+        OutputFormat::Json | OutputFormat::JsonLines => io::write_json(
+            &first.modules,
+            &significant_cores,
+            &insignificant_modules,
+            &scores,
+            &labels,
+            &mut io::create(&out_file)?,
+            out_format == OutputFormat::JsonLines,
+        )?,
+    }
+    eprintln!("done");
+
+    if let Some(frequencies_file) = frequencies_file {
+        eprint!("Writing core frequencies... ");
+        io::write_frequencies(
+            &frequencies,
+            &insignificant_modules,
+            &labels,
+            &mut io::create(&frequencies_file)?,
+        )?;
+        eprintln!("done");
+    }
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    extern crate test;
+/// Prints the validation issues, and fails on any with `strict`.
+#[cfg(not(target_arch = "wasm32"))]
+fn report_issues(issues: &[validation::Issue], strict: bool) -> Result<(), &'static str> {
+    eprintln!("done ({} issues)", issues.len());
 
-    use test::Bencher;
+    for issue in issues.iter() {
+        eprintln!("\t- {}", issue);
+    }
 
-    #[bench]
-    fn bench_collect(b: &mut Bencher) {
-        b.iter(|| {
-            let path = "1:2:3".split(':').collect::<Vec<_>>();
-            let _level: u8 = path.len().try_into().unwrap();
-            let _last: u32 = path.last().and_then(|s| s.parse().ok()).unwrap();
-        })
+    if strict && !issues.is_empty() {
+        return Err("Partitions failed validation (--strict)");
     }
 
-    #[bench]
-    fn bench_clone(b: &mut Bencher) {
-        b.iter(|| {
-            let path = "1:2:3".split(':');
-            let _level: u8 = path.clone().count().try_into().unwrap();
-            let _last: u32 = path.last().and_then(|s| s.parse().ok()).unwrap();
-        })
+    Ok(())
+}
+
+/// The confidence independent part of a run, shared by all confidences in a sweep.
+#[cfg(not(target_arch = "wasm32"))]
+struct Clustering<'a> {
+    first: &'a Network,
+    rest: &'a BTreeMap<NetworkId, Network>,
+    most_similar_modules: &'a HashMap<String, BTreeMap<NetworkId, String>>,
+    optimizer: &'a dyn optimizer::CoreOptimizer,
+    seed: u64,
+    consistency: Consistency,
+    missing: clustering::Missing,
+    module_test: clustering::ModuleTest,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Cores {
+    significant_cores: HashMap<String, HashSet<NodeId>>,
+    frequencies: HashMap<String, HashMap<NodeId, f32>>,
+    scores: HashMap<String, HashMap<NodeId, f32>>,
+    /// Modules that fail the module test.
+    insignificant_modules: HashSet<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clustering<'_> {
+    /// Finds the significant core of every module in the reference partition,
+    /// optionally warm started from `initial` cores.
+    fn cluster(
+        &self,
+        conf: &Confidence,
+        initial: Option<&HashMap<String, HashSet<NodeId>>>,
+    ) -> Cores {
+        let Clustering {
+            first,
+            rest,
+            most_similar_modules,
+            optimizer,
+            seed,
+            consistency,
+            missing,
+            module_test,
+        } = *self;
+
+        let num_modules = most_similar_modules.len();
+        eprint!("Clustering... 0/{} done", num_modules);
+        std::io::stderr().flush().unwrap();
+        let start = Instant::now();
+        let current_count = Arc::new(AtomicUsize::new(0));
+
+        // Cluster one level at a time so that each module can see its parent's core
+        let mut levels = BTreeMap::new();
+        for module_id in most_similar_modules.keys() {
+            levels
+                .entry(first.modules[module_id].level)
+                .or_insert_with(Vec::new)
+                .push(module_id);
+        }
+
+        let mut significant_cores = HashMap::new();
+        let mut frequencies = HashMap::new();
+        let mut scores = HashMap::new();
+        let mut absent_nodes = HashMap::new();
+
+        for module_ids in levels.values() {
+            let results = module_ids
+                .par_iter()
+                .map(|&module_id1| {
+                    let module = &first.modules[module_id1];
+
+                    let modules = most_similar_modules[module_id1]
+                        .iter()
+                        .map(|(network_id, module_id)| &rest[network_id].modules[module_id].nodes)
+                        .collect::<Vec<_>>();
+
+                    // Nodes in the module that are absent from each bootstrap partition
+                    let absent = match missing {
+                        clustering::Missing::Exclude => Vec::new(),
+                        _ => most_similar_modules[module_id1]
+                            .keys()
+                            .map(|network_id| {
+                                let nodes = &rest[network_id].nodes;
+                                module
+                                    .nodes
+                                    .iter()
+                                    .filter(|node| !nodes.contains(node))
+                                    .copied()
+                                    .collect()
+                            })
+                            .collect(),
+                    };
+
+                    let params = optimizer::Params {
+                        conf: conf.for_level(module.level),
+                        seed,
+                        missing,
+                        absent: &absent,
+                        initial: initial.and_then(|cores| cores.get(module_id1)),
+                    };
+
+                    let parent_core = module
+                        .parent_id()
+                        .and_then(|parent_id| significant_cores.get(parent_id));
+
+                    let result = match parent_core {
+                        Some(parent_core) if consistency == Consistency::Restrict => {
+                            let candidates = module
+                                .nodes
+                                .intersection(parent_core)
+                                .copied()
+                                .collect::<HashSet<_>>();
+                            optimizer.optimize(&candidates, &modules, &params)
+                        }
+                        _ => optimizer.optimize(&module.nodes, &modules, &params),
+                    };
+
+                    let count = current_count.fetch_add(1, Ordering::SeqCst);
+                    eprint!("\rClustering... {}/{} done", count, num_modules);
+                    std::io::stderr().flush().unwrap();
+
+                    let support = clustering::get_support(&module.nodes, &modules);
+
+                    (module_id1.to_string(), result, support, absent)
+                })
+                .collect::<Vec<_>>();
+
+            for (module_id, result, support, absent) in results.into_iter() {
+                absent_nodes.insert(module_id.clone(), absent);
+                scores.insert(module_id.clone(), support);
+                significant_cores.insert(module_id.clone(), result.core);
+                frequencies.insert(module_id, result.diagnostics.frequencies);
+            }
+        }
+
+        eprintln!(
+            "\rClustering... {}/{} done ({} ms)",
+            num_modules,
+            num_modules,
+            start.elapsed().as_millis()
+        );
+
+        if consistency == Consistency::Propagate {
+            clustering::propagate_insignificance(&first.modules, &mut significant_cores);
+        }
+
+        // Test the modules on the final cores, after any propagation
+        let insignificant_modules = most_similar_modules
+            .iter()
+            .filter(|(module_id, matches)| {
+                let module = &first.modules[*module_id];
+                let modules = matches
+                    .iter()
+                    .map(|(network_id, other)| &rest[network_id].modules[other].nodes)
+                    .collect::<Vec<_>>();
+
+                !module_test.is_significant(
+                    &module.nodes,
+                    &significant_cores[*module_id],
+                    &modules,
+                    &absent_nodes[*module_id],
+                    conf.for_level(module.level),
+                )
+            })
+            .map(|(module_id, _)| module_id.clone())
+            .collect::<HashSet<_>>();
+
+        if module_test != clustering::ModuleTest::None {
+            eprintln!(
+                "{} of {} modules are not significant",
+                insignificant_modules.len(),
+                num_modules
+            );
+        }
+
+        Cores {
+            significant_cores,
+            frequencies,
+            scores,
+            insignificant_modules,
+        }
+    }
+}
+
+/// Reads the reference partition and each bootstrap partition from its own
+/// file, reporting nodes that don't match the reference.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_bootstrap_files(
+    in_file: &str,
+    bootstraps: &[String],
+) -> Result<(BTreeMap<NetworkId, Network>, io::Labels), Box<dyn std::error::Error>> {
+    let mut labels = io::Labels::new();
+    let mut networks = BTreeMap::new();
+
+    networks.insert(0, io::read_file(in_file, &mut labels)?);
+
+    let mut files = Vec::new();
+    for pattern in bootstraps.iter() {
+        files.extend(io::find_files(pattern)?);
+    }
+
+    if files.is_empty() {
+        return Err("No bootstrap partition files found".into());
+    }
+
+    for (i, file) in files.iter().enumerate() {
+        let mut network = io::read_file(file, &mut labels)?;
+        let mismatch = io::join(&networks[&0], &mut network);
+
+        if !mismatch.missing.is_empty() || !mismatch.unknown.is_empty() {
+            let examples = |nodes: &[NodeId]| {
+                nodes
+                    .iter()
+                    .take(5)
+                    .map(|&node| labels.label(node))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            eprint!(
+                "\n\t{}: {} reference nodes missing ({}), {} unknown nodes removed ({})",
+                file,
+                mismatch.missing.len(),
+                examples(&mismatch.missing),
+                mismatch.unknown.len(),
+                examples(&mismatch.unknown),
+            );
+        }
+
+        networks.insert(i + 1, network);
+    }
+
+    eprint!("\n\t{} bootstrap partitions... ", files.len());
+
+    Ok((networks, labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistency_restrict() {
+        // Nodes a and b form their own top module in every bootstrap, so the
+        // parent core leaves them out while the submodule 1:1 matches them exactly
+        let input = "a 1:1 2:1 2:1\nb 1:1 2:1 2:1\nc 1:2 1:1 1:1\nd 1:2 1:1 1:1\n\
+                     e 1:2 1:2 1:2\nf 1:2 1:2 1:2\n";
+        let (mut networks, labels) = io::read_input(input).unwrap();
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = similarity::get_most_similar_modules(&first, &networks);
+        let node = |label| labels.get(label).unwrap();
+
+        let cores = |consistency| {
+            Clustering {
+                first: &first,
+                rest: &networks,
+                most_similar_modules: &most_similar_modules,
+                optimizer: &optimizer::Greedy,
+                seed: 123,
+                consistency,
+                missing: clustering::Missing::Exclude,
+                module_test: clustering::ModuleTest::None,
+            }
+            .cluster(&Confidence::new(0.95), None)
+            .significant_cores
+        };
+
+        let independent = cores(Consistency::None);
+        assert!(!independent["1"].contains(&node("a")));
+        assert_eq!(independent["1:1"], HashSet::from([node("a"), node("b")]));
+
+        let restricted = cores(Consistency::Restrict);
+        assert_eq!(restricted["1"], independent["1"]);
+        assert!(restricted["1:1"].is_empty());
+        assert_eq!(restricted["1:2"], independent["1:2"]);
+    }
+
+    #[test]
+    fn test_module_test_propagate() {
+        // Same partitions as above, where propagation empties the core of 1:1
+        let input = "a 1:1 2:1 2:1\nb 1:1 2:1 2:1\nc 1:2 1:1 1:1\nd 1:2 1:1 1:1\n\
+                     e 1:2 1:2 1:2\nf 1:2 1:2 1:2\n";
+        let (mut networks, _) = io::read_input(input).unwrap();
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = similarity::get_most_similar_modules(&first, &networks);
+
+        let cores = |consistency| {
+            Clustering {
+                first: &first,
+                rest: &networks,
+                most_similar_modules: &most_similar_modules,
+                optimizer: &optimizer::Greedy,
+                seed: 123,
+                consistency,
+                missing: clustering::Missing::Exclude,
+                module_test: clustering::ModuleTest::CoreFraction(0.5),
+            }
+            .cluster(&Confidence::new(0.95), None)
+        };
+
+        assert!(!cores(Consistency::None)
+            .insignificant_modules
+            .contains("1:1"));
+
+        let propagated = cores(Consistency::Propagate);
+        assert!(propagated.significant_cores["1:1"].is_empty());
+        assert!(propagated.insignificant_modules.contains("1:1"));
     }
 }
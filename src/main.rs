@@ -7,7 +7,7 @@ fn main() {
     });
 
     if let Err(e) = significance_clustering::run(config) {
-        println!("Application error: {}", e);
+        eprintln!("Application error: {}", e);
         std::process::exit(1);
     }
 }
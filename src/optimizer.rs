@@ -0,0 +1,392 @@
+use std::borrow::Cow;
+use std::cmp::max;
+
+use crate::clustering::{self, Missing, Objective, ObjectiveFn, Scorer};
+use crate::{HashMap, HashSet, NodeId};
+
+/// Parameters shared by all core optimizers.
+#[derive(Debug, Clone, Copy)]
+pub struct Params<'a> {
+    pub conf: f32,
+    pub seed: u64,
+    /// How nodes absent from a bootstrap partition count.
+    pub missing: Missing,
+    /// Nodes absent from the partition of each matched module, if any.
+    pub absent: &'a [HashSet<NodeId>],
+    /// Core to start from, for optimizers that can be warm started.
+    pub initial: Option<&'a HashSet<NodeId>>,
+}
+
+impl<'a> Params<'a> {
+    pub fn new(conf: f32, seed: u64) -> Self {
+        Self {
+            conf,
+            seed,
+            missing: Missing::Exclude,
+            absent: &[],
+            initial: None,
+        }
+    }
+
+    /// The matched modules with the absent nodes added back, for optimizers
+    /// that only need to know if a node counts against a module.
+    fn fill_absent<'m>(&self, modules: &[&'m HashSet<NodeId>]) -> Vec<Cow<'m, HashSet<NodeId>>> {
+        modules
+            .iter()
+            .enumerate()
+            .map(|(i, &module)| match self.absent.get(i) {
+                Some(absent) if self.missing != Missing::Exclude && !absent.is_empty() => {
+                    Cow::Owned(module.union(absent).copied().collect())
+                }
+                _ => Cow::Borrowed(module),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    /// Score of the core under the optimizer's objective.
+    pub score: i64,
+    /// Penalty of the core under the optimizer's objective.
+    pub penalty: i64,
+    /// Fraction of restarts that put each node in the core, if restarted.
+    pub frequencies: HashMap<NodeId, f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CoreResult {
+    pub core: HashSet<NodeId>,
+    pub diagnostics: Diagnostics,
+}
+
+/// Finds the significant core of a module given its most similar modules
+/// in the bootstrap partitions.
+pub trait CoreOptimizer: Send + Sync {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult;
+}
+
+fn evaluate(
+    core: HashSet<NodeId>,
+    modules: &[&HashSet<NodeId>],
+    objective: &dyn Objective,
+) -> CoreResult {
+    let (score, penalty) = objective.score(&core, modules);
+
+    CoreResult {
+        core,
+        diagnostics: Diagnostics {
+            score,
+            penalty,
+            ..Default::default()
+        },
+    }
+}
+
+/// Simulated annealing, see [`clustering::get_significant_core`].
+pub struct Annealing {
+    pub objective: ObjectiveFn,
+}
+
+impl Default for Annealing {
+    fn default() -> Self {
+        Self {
+            objective: Scorer::boxed,
+        }
+    }
+}
+
+impl CoreOptimizer for Annealing {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult {
+        let objective = (self.objective)(module, modules, params);
+        let core = clustering::anneal(
+            module,
+            modules,
+            objective.as_ref(),
+            params.seed,
+            params.initial,
+        );
+        evaluate(core, modules, objective.as_ref())
+    }
+}
+
+/// Greedy exclusion of matched modules, see [`clustering::get_significant_core_greedy`].
+///
+/// Always optimizes the default [`Scorer`] objective.
+pub struct Greedy;
+
+impl CoreOptimizer for Greedy {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult {
+        let filled = params.fill_absent(modules);
+        let filled = filled.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let core = clustering::get_significant_core_greedy(module, &filled, params.conf);
+        evaluate(
+            core,
+            modules,
+            Scorer::boxed(module, modules, params).as_ref(),
+        )
+    }
+}
+
+/// Annealing warm started from the greedy core, unless given a core to start from.
+#[derive(Default)]
+pub struct GreedyAnnealing {
+    pub annealing: Annealing,
+}
+
+impl CoreOptimizer for GreedyAnnealing {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult {
+        let initial = match params.initial {
+            Some(initial) => Cow::Borrowed(initial),
+            None => {
+                let filled = params.fill_absent(modules);
+                let filled = filled.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+                Cow::Owned(clustering::get_significant_core_greedy(
+                    module,
+                    &filled,
+                    params.conf,
+                ))
+            }
+        };
+        let objective = (self.annealing.objective)(module, modules, params);
+        let core = clustering::anneal(
+            module,
+            modules,
+            objective.as_ref(),
+            params.seed,
+            Some(&initial),
+        );
+        evaluate(core, modules, objective.as_ref())
+    }
+}
+
+/// Exhaustive search, see [`clustering::get_significant_core_exact`].
+///
+/// Always optimizes the default [`Scorer`] objective.
+pub struct Exact;
+
+impl CoreOptimizer for Exact {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult {
+        let filled = params.fill_absent(modules);
+        let filled = filled.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let core = clustering::get_significant_core_exact(module, &filled, params.conf);
+        evaluate(
+            core,
+            modules,
+            Scorer::boxed(module, modules, params).as_ref(),
+        )
+    }
+}
+
+/// Runs `optimizer` with `restarts` consecutive seeds and keeps the best core.
+///
+/// The diagnostics hold the fraction of restarts that put each node in the core.
+pub struct Restarts<O> {
+    pub optimizer: O,
+    pub restarts: usize,
+}
+
+impl<O> Restarts<O> {
+    pub fn new(optimizer: O, restarts: usize) -> Self {
+        Self {
+            optimizer,
+            restarts: max(restarts, 1),
+        }
+    }
+}
+
+impl<O: CoreOptimizer> CoreOptimizer for Restarts<O> {
+    fn optimize(
+        &self,
+        module: &HashSet<NodeId>,
+        modules: &[&HashSet<NodeId>],
+        params: &Params,
+    ) -> CoreResult {
+        let mut counts = module
+            .iter()
+            .map(|&node| (node, 0))
+            .collect::<HashMap<_, usize>>();
+
+        let mut best: Option<CoreResult> = None;
+
+        for restart in 0..self.restarts {
+            let params = Params {
+                seed: params.seed.wrapping_add(restart as u64),
+                ..*params
+            };
+
+            let result = self.optimizer.optimize(module, modules, &params);
+
+            for node in result.core.iter() {
+                *counts.get_mut(node).unwrap() += 1;
+            }
+
+            // Prefer cores without penalty, then the highest score
+            let key = |r: &CoreResult| (-r.diagnostics.penalty, r.diagnostics.score);
+
+            match best {
+                Some(ref best) if key(best) >= key(&result) => {}
+                _ => best = Some(result),
+            }
+        }
+
+        let mut best = best.unwrap();
+
+        best.diagnostics.frequencies = counts
+            .into_iter()
+            .map(|(node, count)| (node, count as f32 / self.restarts as f32))
+            .collect();
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::{hash_set, vec};
+    use proptest::prelude::*;
+
+    fn optimizers() -> Vec<Box<dyn CoreOptimizer>> {
+        vec![
+            Box::new(Annealing::default()),
+            Box::new(Greedy),
+            Box::new(GreedyAnnealing::default()),
+            Box::new(Exact),
+            Box::new(Restarts::new(Annealing::default(), 3)),
+        ]
+    }
+
+    /// A module and matched modules that each keep most of its nodes and add a few others.
+    fn partitions() -> impl Strategy<Value = (HashSet<NodeId>, Vec<HashSet<NodeId>>)> {
+        let module = hash_set(0..16 as NodeId, 1..16);
+        let modules = vec(
+            (
+                vec(prop::bool::weighted(0.85), 16),
+                hash_set(0..24 as NodeId, 0..4),
+            ),
+            1..8,
+        );
+
+        (module, modules).prop_map(|(module, modules)| {
+            let module = module.into_iter().collect::<HashSet<_>>();
+            let modules = modules
+                .into_iter()
+                .map(|(keep, extra)| {
+                    module
+                        .iter()
+                        .filter(|&&node| keep[node as usize])
+                        .copied()
+                        .chain(extra)
+                        .collect::<HashSet<_>>()
+                })
+                .collect();
+            (module, modules)
+        })
+    }
+
+    fn intersection(module: &HashSet<NodeId>, modules: &[&HashSet<NodeId>]) -> HashSet<NodeId> {
+        module
+            .iter()
+            .filter(|node| modules.iter().all(|m| m.contains(node)))
+            .copied()
+            .collect()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_core_invariants(
+            (module, modules) in partitions(),
+            conf in 0.5f32..=1.0,
+            seed: u64,
+        ) {
+            let modules = modules.iter().collect::<Vec<_>>();
+            let in_all = intersection(&module, &modules);
+
+            for optimizer in optimizers() {
+                let core = optimizer.optimize(&module, &modules, &Params::new(conf, seed)).core;
+                prop_assert!(core.is_subset(&module));
+                prop_assert!(in_all.is_subset(&core));
+
+                let core = optimizer.optimize(&module, &modules, &Params::new(1.0, seed)).core;
+                prop_assert_eq!(&core, &in_all);
+            }
+        }
+
+        #[test]
+        fn prop_exact_monotonic(
+            (module, modules) in partitions(),
+            conf1 in 0.5f32..=1.0,
+            conf2 in 0.5f32..=1.0,
+        ) {
+            let modules = modules.iter().collect::<Vec<_>>();
+            let (low, high) = if conf1 < conf2 { (conf1, conf2) } else { (conf2, conf1) };
+
+            let low = Exact.optimize(&module, &modules, &Params::new(low, 123)).core;
+            let high = Exact.optimize(&module, &modules, &Params::new(high, 123)).core;
+            prop_assert!(high.len() <= low.len());
+        }
+
+        #[test]
+        fn prop_scorer(
+            (module, modules) in partitions(),
+            conf in 0.5f32..=1.0,
+        ) {
+            let modules = modules.iter().collect::<Vec<_>>();
+            let scorer = Scorer::for_module(&module, &modules, conf);
+
+            // Nodes in all matched modules never count against the core
+            let (_, penalty) = scorer.score(&intersection(&module, &modules), &modules);
+            prop_assert_eq!(penalty, 0);
+
+            let (score, penalty) = scorer.score(&HashSet::new(), &modules);
+            prop_assert_eq!((score, penalty), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_optimizers() {
+        let module = (0..10).collect::<HashSet<NodeId>>();
+        let modules = [
+            (0..10).collect::<HashSet<_>>(),
+            (1..11).collect::<HashSet<_>>(),
+            (0..10).collect::<HashSet<_>>(),
+        ];
+        let modules = modules.iter().collect::<Vec<_>>();
+        let params = Params::new(0.95, 123);
+
+        for optimizer in optimizers() {
+            let result = optimizer.optimize(&module, &modules, &params);
+            assert_eq!(result.core, (1..10).collect::<HashSet<_>>());
+            assert_eq!(result.diagnostics.score, 27);
+            assert_eq!(result.diagnostics.penalty, 0);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::bootstrap::{LinkList, Partitioner};
+use crate::{HashMap, Network, NodeId};
+
+/// Louvain modularity optimization, applied again within each module to
+/// get a hierarchical partition.
+///
+/// Meant for generating bootstrap partitions in tests and end-to-end runs,
+/// not as a replacement for Infomap.
+#[derive(Debug, Clone, Copy)]
+pub struct Louvain {
+    /// Deepest level of the partition.
+    pub max_depth: u8,
+    /// Modules with fewer nodes are not split further.
+    pub min_size: usize,
+}
+
+impl Default for Louvain {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            min_size: 4,
+        }
+    }
+}
+
+/// Undirected weighted graph over nodes `0..n`.
+struct Graph {
+    /// Neighbours and link weights, with every link in both directions.
+    adjacency: Vec<Vec<(usize, f64)>>,
+    /// Twice the weight of self-links, to count them like other links.
+    self_weights: Vec<f64>,
+}
+
+impl Graph {
+    fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    fn strength(&self, node: usize) -> f64 {
+        self.self_weights[node] + self.adjacency[node].iter().map(|&(_, w)| w).sum::<f64>()
+    }
+
+    /// Moves nodes between modules while modularity improves, returning the
+    /// module of each node, numbered from zero.
+    fn local_moving(&self, rng: &mut StdRng) -> Vec<usize> {
+        let strengths = (0..self.len())
+            .map(|node| self.strength(node))
+            .collect::<Vec<_>>();
+        let total = strengths.iter().sum::<f64>();
+
+        let mut modules = (0..self.len()).collect::<Vec<_>>();
+        let mut module_strengths = strengths.clone();
+        let mut order = (0..self.len()).collect::<Vec<_>>();
+        let mut weights = HashMap::new();
+
+        if total <= 0.0 {
+            return modules;
+        }
+
+        loop {
+            let mut moved = false;
+            order.shuffle(rng);
+
+            for &node in order.iter() {
+                let old_module = modules[node];
+                module_strengths[old_module] -= strengths[node];
+
+                weights.clear();
+                weights.insert(old_module, 0.0);
+                for &(neighbour, w) in self.adjacency[node].iter() {
+                    *weights.entry(modules[neighbour]).or_insert(0.0) += w;
+                }
+
+                let gain = |module: usize, weight: f64| {
+                    weight - module_strengths[module] * strengths[node] / total
+                };
+
+                let mut best = (old_module, gain(old_module, weights[&old_module]));
+                for (&module, &weight) in weights.iter() {
+                    let candidate = (module, gain(module, weight));
+                    // Ties go to the lowest module for deterministic results
+                    if candidate.1 > best.1 + 1e-12
+                        || (candidate.1 > best.1 - 1e-12 && candidate.0 < best.0)
+                    {
+                        best = candidate;
+                    }
+                }
+
+                modules[node] = best.0;
+                module_strengths[best.0] += strengths[node];
+                moved |= best.0 != old_module;
+            }
+
+            if !moved {
+                break;
+            }
+        }
+
+        renumber(&mut modules);
+        modules
+    }
+
+    /// The graph with one node per module.
+    fn aggregate(&self, modules: &[usize]) -> Graph {
+        let num_modules = modules.iter().max().map_or(0, |&max| max + 1);
+        let mut links = vec![HashMap::new(); num_modules];
+        let mut self_weights = vec![0.0; num_modules];
+
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            let module = modules[node];
+            self_weights[module] += self.self_weights[node];
+
+            for &(neighbour, w) in neighbours.iter() {
+                match modules[neighbour] {
+                    other if other == module => self_weights[module] += w,
+                    other => *links[module].entry(other).or_insert(0.0) += w,
+                }
+            }
+        }
+
+        let adjacency = links
+            .into_iter()
+            .map(|links| {
+                let mut links = links.into_iter().collect::<Vec<_>>();
+                links.sort_by_key(|&(module, _)| module);
+                links
+            })
+            .collect();
+
+        Graph {
+            adjacency,
+            self_weights,
+        }
+    }
+
+    /// Louvain: local moving and aggregation until no module merges.
+    fn louvain(&self, rng: &mut StdRng) -> Vec<usize> {
+        let mut modules = (0..self.len()).collect::<Vec<_>>();
+        let mut graph = None;
+
+        loop {
+            let current = graph.as_ref().unwrap_or(self);
+            let level = current.local_moving(rng);
+            let num_modules = level.iter().max().map_or(0, |&max| max + 1);
+
+            for module in modules.iter_mut() {
+                *module = level[*module];
+            }
+
+            if num_modules == current.len() {
+                break;
+            }
+
+            graph = Some(current.aggregate(&level));
+        }
+
+        modules
+    }
+}
+
+/// Numbers modules from zero in order of first appearance.
+fn renumber(modules: &mut [usize]) {
+    let mut ids = HashMap::new();
+
+    for module in modules.iter_mut() {
+        let next = ids.len();
+        *module = *ids.entry(*module).or_insert(next);
+    }
+}
+
+/// The undirected graph between `nodes`, ignoring links to other nodes.
+fn induced_graph(links: &LinkList, nodes: &[NodeId]) -> Graph {
+    let index = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, &node)| (node, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut adjacency = vec![Vec::new(); nodes.len()];
+    let mut self_weights = vec![0.0; nodes.len()];
+
+    for &(source, target, weight) in links.links.iter() {
+        if let (Some(&source), Some(&target)) = (index.get(&source), index.get(&target)) {
+            if source == target {
+                self_weights[source] += 2.0 * weight;
+            } else {
+                adjacency[source].push((target, weight));
+                adjacency[target].push((source, weight));
+            }
+        }
+    }
+
+    Graph {
+        adjacency,
+        self_weights,
+    }
+}
+
+impl Louvain {
+    fn partition_nodes(
+        &self,
+        links: &LinkList,
+        nodes: &[NodeId],
+        path: &str,
+        depth: u8,
+        rng: &mut StdRng,
+        network: &mut Network,
+    ) {
+        let graph = induced_graph(links, nodes);
+        let modules = graph.louvain(rng);
+
+        let mut submodules = vec![Vec::new(); modules.iter().max().map_or(0, |&max| max + 1)];
+        for (&node, &module) in nodes.iter().zip(modules.iter()) {
+            submodules[module].push(node);
+        }
+
+        // Largest module first, as in Infomap
+        submodules.sort_by_key(|nodes| std::cmp::Reverse(nodes.len()));
+
+        // Don't add a level with a single module below the top level
+        if depth > 1 && submodules.len() < 2 {
+            return;
+        }
+
+        for (i, nodes) in submodules.iter().enumerate() {
+            let module_id = match path {
+                "" => (i + 1).to_string(),
+                _ => format!("{}:{}", path, i + 1),
+            };
+
+            for &node in nodes.iter() {
+                network.add_node(&module_id, node);
+            }
+
+            if depth < self.max_depth && nodes.len() >= self.min_size {
+                self.partition_nodes(links, nodes, &module_id, depth + 1, rng, network);
+            }
+        }
+    }
+}
+
+impl Partitioner for Louvain {
+    fn partition(&self, links: &LinkList, seed: u64) -> Network {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut network = Network::new();
+
+        let mut nodes = links.nodes().into_iter().collect::<Vec<_>>();
+        nodes.sort_unstable();
+
+        if self.max_depth > 0 {
+            self.partition_nodes(links, &nodes, "", 1, &mut rng, &mut network);
+        }
+
+        network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four cliques of four nodes in two pairs, each pair and the two pairs
+    /// joined by a single link.
+    fn cliques() -> LinkList {
+        let mut links = Vec::new();
+
+        for clique in 0..4 {
+            let nodes = (0..4).map(|i| clique * 4 + i).collect::<Vec<NodeId>>();
+            for (i, &source) in nodes.iter().enumerate() {
+                for &target in nodes[i + 1..].iter() {
+                    links.push((source, target, 1.0));
+                }
+            }
+        }
+
+        links.push((0, 4, 1.0));
+        links.push((8, 12, 1.0));
+        links.push((3, 11, 0.1));
+
+        LinkList {
+            links,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_louvain() {
+        let links = cliques();
+        let louvain = Louvain {
+            max_depth: 1,
+            min_size: 4,
+        };
+
+        let network = louvain.partition(&links, 1);
+        assert_eq!(network.nodes.len(), 16);
+        assert_eq!(network.modules.len(), 4);
+
+        for module in network.modules.values() {
+            let mut nodes = module.nodes.iter().copied().collect::<Vec<_>>();
+            nodes.sort_unstable();
+            assert_eq!(nodes.len(), 4);
+            assert_eq!(nodes[0] % 4, 0);
+            assert_eq!(nodes[3], nodes[0] + 3);
+        }
+
+        let again = louvain.partition(&links, 1);
+        for (module_id, module) in network.modules.iter() {
+            assert_eq!(again.modules[module_id].nodes, module.nodes);
+        }
+    }
+
+    #[test]
+    fn test_louvain_hierarchy() {
+        let mut links = Vec::new();
+
+        // Eight pairs of four-cliques, with each node linked to its
+        // counterpart in the other clique of the pair
+        for clique in 0..16 {
+            let nodes = (0..4).map(|i| clique * 4 + i).collect::<Vec<NodeId>>();
+            for (i, &source) in nodes.iter().enumerate() {
+                for &target in nodes[i + 1..].iter() {
+                    links.push((source, target, 1.0));
+                }
+                if clique % 2 == 0 {
+                    links.push((source, source + 4, 0.5));
+                }
+            }
+        }
+
+        // Chain the pairs together
+        for pair in 0..7 {
+            links.push((pair * 8, pair * 8 + 8, 0.1));
+        }
+
+        let links = LinkList {
+            links,
+            ..Default::default()
+        };
+        let network = Louvain::default().partition(&links, 1);
+
+        let top = network.modules.values().filter(|m| m.level == 1).count();
+        let sub = network.modules.values().filter(|m| m.level == 2).count();
+        assert_eq!(top, 8);
+        assert_eq!(sub, 16);
+
+        for module in network.modules.values() {
+            let expected = if module.level == 1 { 8 } else { 4 };
+            assert_eq!(module.nodes.len(), expected);
+        }
+
+        assert!(network.modules["1:2"]
+            .nodes
+            .is_subset(&network.modules["1"].nodes));
+    }
+}
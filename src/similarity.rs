@@ -1,5 +1,7 @@
 use std::collections::BTreeMap;
 
+use itertools::Itertools;
+
 use crate::{HashMap, HashSet, Network, NetworkId, NodeId};
 
 pub fn get_most_similar_modules(
@@ -34,7 +36,7 @@ pub fn get_most_similar_modules(
         .collect()
 }
 
-fn jaccard_distance(m1: &HashSet<NodeId>, m2: &HashSet<NodeId>) -> f32 {
+pub fn jaccard_distance(m1: &HashSet<NodeId>, m2: &HashSet<NodeId>) -> f32 {
     let jaccard_index = match m1.union(m2).count() {
         0 => 0.0,
         union => {
@@ -45,3 +47,325 @@ fn jaccard_distance(m1: &HashSet<NodeId>, m2: &HashSet<NodeId>) -> f32 {
 
     1.0 - jaccard_index
 }
+
+/// How similar a bootstrap partition is to the reference on one level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PartitionSimilarity {
+    /// Normalized mutual information, with the arithmetic mean of the entropies.
+    pub nmi: f64,
+    /// Adjusted Rand index.
+    pub ari: f64,
+    /// Mean Jaccard index of the reference modules on the level and their
+    /// most similar bootstrap modules, weighted by module size.
+    pub weighted_jaccard: f64,
+}
+
+/// The module of each node on each level, from the top. Nodes in leaf modules
+/// above a level are in the same module on that level.
+fn level_modules(network: &Network) -> HashMap<NodeId, Vec<&str>> {
+    let mut paths: HashMap<NodeId, Vec<&str>> = HashMap::new();
+
+    for module in network
+        .modules
+        .values()
+        .sorted_by_key(|module| module.level)
+    {
+        for &node in module.nodes.iter() {
+            paths.entry(node).or_default().push(&module.module_id);
+        }
+    }
+
+    paths
+}
+
+fn module_on_level<'a>(path: &[&'a str], level: u8) -> &'a str {
+    path[(level as usize).min(path.len()) - 1]
+}
+
+/// NMI and ARI of two partitions, given as the module of each node.
+fn compare_partitions<'a>(modules: impl Iterator<Item = (&'a str, &'a str)>) -> (f64, f64) {
+    let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut rows: HashMap<&str, usize> = HashMap::new();
+    let mut cols: HashMap<&str, usize> = HashMap::new();
+    let mut n = 0;
+
+    for (module1, module2) in modules {
+        *counts.entry((module1, module2)).or_default() += 1;
+        *rows.entry(module1).or_default() += 1;
+        *cols.entry(module2).or_default() += 1;
+        n += 1;
+    }
+
+    if n == 0 {
+        return (1.0, 1.0);
+    }
+
+    let total = n as f64;
+    let entropy = |sizes: &HashMap<&str, usize>| {
+        sizes
+            .values()
+            .map(|&size| size as f64 / total)
+            .map(|p| -p * p.ln())
+            .sum::<f64>()
+    };
+
+    let mutual_information = counts
+        .iter()
+        .map(|(&(module1, module2), &count)| {
+            let count = count as f64;
+            count / total * (total * count / (rows[module1] * cols[module2]) as f64).ln()
+        })
+        .sum::<f64>();
+
+    let entropies = entropy(&rows) + entropy(&cols);
+    let nmi = if entropies > 0.0 {
+        2.0 * mutual_information / entropies
+    } else {
+        1.0
+    };
+
+    let pairs = |count: usize| (count * count.saturating_sub(1) / 2) as f64;
+    let index = counts.values().map(|&count| pairs(count)).sum::<f64>();
+    let row_pairs = rows.values().map(|&count| pairs(count)).sum::<f64>();
+    let col_pairs = cols.values().map(|&count| pairs(count)).sum::<f64>();
+    let expected = row_pairs * col_pairs / pairs(n);
+    let max = (row_pairs + col_pairs) / 2.0;
+    let ari = if max > expected {
+        (index - expected) / (max - expected)
+    } else {
+        1.0
+    };
+
+    (nmi, ari)
+}
+
+/// Compares each bootstrap partition to the reference on each level of the
+/// reference, over the nodes in both partitions.
+pub fn get_partition_similarities(
+    first: &Network,
+    rest: &BTreeMap<NetworkId, Network>,
+    most_similar_modules: &HashMap<String, BTreeMap<NetworkId, String>>,
+) -> BTreeMap<NetworkId, BTreeMap<u8, PartitionSimilarity>> {
+    let reference = level_modules(first);
+    let levels = first.modules.values().map(|module| module.level).max();
+
+    rest.iter()
+        .map(|(&network_id, network)| {
+            let bootstrap = level_modules(network);
+
+            let similarities = (1..=levels.unwrap_or(0))
+                .map(|level| {
+                    let (nmi, ari) =
+                        compare_partitions(reference.iter().filter_map(|(node, path1)| {
+                            let path2 = bootstrap.get(node)?;
+                            Some((module_on_level(path1, level), module_on_level(path2, level)))
+                        }));
+
+                    let (weighted, size) = first
+                        .modules
+                        .values()
+                        .filter(|module| module.level == level)
+                        .filter_map(|module| {
+                            let other = most_similar_modules
+                                .get(&module.module_id)?
+                                .get(&network_id)?;
+                            let distance =
+                                jaccard_distance(&module.nodes, &network.modules[other].nodes);
+                            let size = module.nodes.len() as f64;
+                            Some((size * (1.0 - distance) as f64, size))
+                        })
+                        .fold((0.0, 0.0), |(weighted, total), (jaccard, size)| {
+                            (weighted + jaccard, total + size)
+                        });
+
+                    let similarity = PartitionSimilarity {
+                        nmi,
+                        ari,
+                        weighted_jaccard: if size > 0.0 { weighted / size } else { 0.0 },
+                    };
+
+                    (level, similarity)
+                })
+                .collect();
+
+            (network_id, similarities)
+        })
+        .collect()
+}
+
+/// Mean NMI of the bootstrap partitions with the reference on each level.
+pub fn mean_nmi(
+    similarities: &BTreeMap<NetworkId, BTreeMap<u8, PartitionSimilarity>>,
+) -> BTreeMap<u8, f64> {
+    let mut nmis = BTreeMap::new();
+
+    for (&level, similarity) in similarities.values().flatten() {
+        let (sum, count) = nmis.entry(level).or_insert((0.0, 0));
+        *sum += similarity.nmi;
+        *count += 1;
+    }
+
+    nmis.into_iter()
+        .map(|(level, (sum, count))| (level, sum / count as f64))
+        .collect()
+}
+
+/// Outliers have an NMI more than this many median absolute deviations
+/// below the median.
+pub const OUTLIER_DEVIATIONS: f64 = 3.0;
+
+/// Smallest median absolute deviation, so that nearly identical bootstrap
+/// partitions do not make small differences outliers.
+pub const MIN_DEVIATION: f64 = 0.05;
+
+/// A bootstrap partition much less similar to the reference than the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outlier {
+    pub network_id: NetworkId,
+    pub level: u8,
+    pub nmi: f64,
+    pub median: f64,
+}
+
+/// Finds bootstrap partitions whose NMI with the reference on a level is
+/// more than [`OUTLIER_DEVIATIONS`] median absolute deviations below the
+/// median, with deviations of at least [`MIN_DEVIATION`].
+pub fn find_outliers(
+    similarities: &BTreeMap<NetworkId, BTreeMap<u8, PartitionSimilarity>>,
+) -> Vec<Outlier> {
+    let median = |values: Vec<f64>| {
+        let values = values
+            .into_iter()
+            .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+            .collect::<Vec<_>>();
+        values.get(values.len() / 2).copied().unwrap_or(0.0)
+    };
+
+    let levels = similarities
+        .values()
+        .flat_map(|levels| levels.keys().copied())
+        .unique()
+        .sorted();
+
+    let mut outliers = Vec::new();
+
+    for level in levels {
+        let nmis = similarities
+            .iter()
+            .filter_map(|(&network_id, levels)| Some((network_id, levels.get(&level)?.nmi)))
+            .collect::<Vec<_>>();
+
+        let median_nmi = median(nmis.iter().map(|&(_, nmi)| nmi).collect());
+        let deviation = median(
+            nmis.iter()
+                .map(|&(_, nmi)| (nmi - median_nmi).abs())
+                .collect(),
+        );
+        let threshold = median_nmi - OUTLIER_DEVIATIONS * deviation.max(MIN_DEVIATION);
+
+        outliers.extend(nmis.into_iter().filter(|&(_, nmi)| nmi < threshold).map(
+            |(network_id, nmi)| Outlier {
+                network_id,
+                level,
+                nmi,
+                median: median_nmi,
+            },
+        ));
+    }
+
+    outliers.sort_by_key(|outlier| (outlier.network_id, outlier.level));
+    outliers
+}
+
+/// Prints the mean NMI on each level and the outlying bootstrap partitions.
+pub fn report(similarities: &BTreeMap<NetworkId, BTreeMap<u8, PartitionSimilarity>>) {
+    eprintln!(
+        "\t- Mean NMI by level: {:.3}",
+        mean_nmi(similarities).values().format(", ")
+    );
+
+    for outlier in find_outliers(similarities) {
+        eprintln!(
+            "\t- Partition {} is an outlier on level {} (NMI {:.3}, median {:.3})",
+            outlier.network_id, outlier.level, outlier.nmi, outlier.median
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::read_input;
+
+    #[test]
+    fn test_compare_partitions() {
+        let (nmi, ari) = compare_partitions([("1", "2"), ("1", "2"), ("2", "1")].into_iter());
+        assert!((nmi - 1.0).abs() < 1e-9);
+        assert!((ari - 1.0).abs() < 1e-9);
+
+        let (nmi, ari) =
+            compare_partitions([("1", "1"), ("1", "2"), ("2", "1"), ("2", "2")].into_iter());
+        assert!(nmi.abs() < 1e-9);
+        assert!((ari + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_partition_similarities() {
+        let input =
+            "a 1:1 1:1 1 2\nb 1:1 1:1 1 2\nc 1:2 1:2 1 1\nd 1:2 1:2 2 1\ne 2 2 2 1\nf 2 2 2 -\n";
+        let (mut networks, _) = read_input(input).unwrap();
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = get_most_similar_modules(&first, &networks);
+
+        let similarities = get_partition_similarities(&first, &networks, &most_similar_modules);
+        assert_eq!(similarities.len(), 3);
+        assert_eq!(similarities[&3].len(), 2);
+
+        // Module 2 has no submodules, so it is the same on level 2
+        let identical = similarities[&1][&2];
+        assert!((identical.nmi - 1.0).abs() < 1e-9);
+        assert!((identical.ari - 1.0).abs() < 1e-9);
+        assert_eq!(identical.weighted_jaccard, 1.0);
+
+        // Node d moved from module 1 to module 2
+        let moved = similarities[&2][&1];
+        let (nmi, ari) = compare_partitions(
+            [
+                ("1", "1"),
+                ("1", "1"),
+                ("1", "1"),
+                ("1", "2"),
+                ("2", "2"),
+                ("2", "2"),
+            ]
+            .into_iter(),
+        );
+        assert_eq!((moved.nmi, moved.ari), (nmi, ari));
+        assert!(moved.nmi < 1.0);
+        assert!((moved.weighted_jaccard - (4.0 * 0.75 + 2.0 * 2.0 / 3.0) / 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_find_outliers() {
+        let similarity = |nmi| {
+            let similarity = PartitionSimilarity {
+                nmi,
+                ari: nmi,
+                weighted_jaccard: nmi,
+            };
+            BTreeMap::from([(1, similarity)])
+        };
+
+        let mut similarities = (1..10)
+            .map(|network_id| (network_id, similarity(0.9 + network_id as f64 / 1000.0)))
+            .collect::<BTreeMap<_, _>>();
+        assert!(find_outliers(&similarities).is_empty());
+
+        similarities.insert(10, similarity(0.5));
+        let outliers = find_outliers(&similarities);
+        assert_eq!(outliers.len(), 1);
+        assert_eq!((outliers[0].network_id, outliers[0].level), (10, 1));
+        assert_eq!(outliers[0].median, 0.905);
+        assert!((mean_nmi(&similarities)[&1] - (9.0 * 0.905 + 0.5) / 10.0).abs() < 1e-9);
+    }
+}
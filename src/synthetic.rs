@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::io::{add_path, Labels};
+use crate::{HashMap, HashSet, Network, NetworkId, NodeId};
+
+/// One level of the planted module hierarchy.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    /// Number of modules in each module on the level above.
+    pub branching: usize,
+    /// Fraction of nodes planted as unstable on this level.
+    pub unstable: f64,
+    /// Probability that an unstable node moves to a sibling module in each bootstrap.
+    pub move_prob: f64,
+}
+
+/// Parameters of a synthetic benchmark: a reference partition with a planted
+/// multi-level module structure and noisy bootstrap partitions of it.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    /// Levels from the top.
+    pub levels: Vec<Level>,
+    /// Nodes per module on the deepest level.
+    pub module_size: usize,
+    pub bootstraps: usize,
+    /// Probability that a module is split in two in each bootstrap.
+    pub split_prob: f64,
+    /// Probability that a module is merged with a sibling in each bootstrap.
+    pub merge_prob: f64,
+    /// Probability that a node is absent from each bootstrap.
+    pub missing_prob: f64,
+    pub seed: u64,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self {
+            levels: vec![
+                Level {
+                    branching: 4,
+                    unstable: 0.1,
+                    move_prob: 0.5,
+                },
+                Level {
+                    branching: 3,
+                    unstable: 0.1,
+                    move_prob: 0.5,
+                },
+            ],
+            module_size: 20,
+            bootstraps: 100,
+            split_prob: 0.01,
+            merge_prob: 0.01,
+            missing_prob: 0.0,
+            seed: 123,
+        }
+    }
+}
+
+/// Generated partitions with the ground truth.
+///
+/// Bootstrap modules keep the ids of the planted modules they come from, so
+/// a correctly matched module has the same id as the reference module.
+#[derive(Debug, Clone)]
+pub struct Synthetic {
+    /// The reference partition as network 0, followed by the bootstraps.
+    pub networks: BTreeMap<NetworkId, Network>,
+    pub labels: Labels,
+    /// Significant nodes of each module in the reference partition.
+    pub truth: HashMap<String, HashSet<NodeId>>,
+}
+
+fn path_id(path: &[usize]) -> String {
+    path.iter()
+        .map(|label| label.to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// A label in `1..=branching` other than `label`.
+fn sibling(label: usize, branching: usize, rng: &mut StdRng) -> usize {
+    let other = rng.gen_range(1..branching);
+    if other >= label {
+        other + 1
+    } else {
+        other
+    }
+}
+
+impl Benchmark {
+    pub fn generate(&self) -> Synthetic {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut labels = Labels::new();
+
+        // Planted path of every node, numbered depth first
+        let num_leaves = self
+            .levels
+            .iter()
+            .map(|level| level.branching)
+            .product::<usize>();
+        let paths = (0..num_leaves * self.module_size)
+            .map(|i| {
+                let mut leaf = i / self.module_size;
+                let mut path = vec![0; self.levels.len()];
+                for (label, level) in path.iter_mut().zip(self.levels.iter()).rev() {
+                    *label = leaf % level.branching + 1;
+                    leaf /= level.branching;
+                }
+                let node = labels.intern(&(i + 1).to_string());
+                (node, path)
+            })
+            .collect::<Vec<_>>();
+
+        // Levels on which each node is unstable
+        let unstable = paths
+            .iter()
+            .map(|_| {
+                self.levels
+                    .iter()
+                    .map(|level| level.branching > 1 && rng.gen_bool(level.unstable))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut networks = BTreeMap::new();
+        let mut reference = Network::new();
+        let mut truth = HashMap::new();
+
+        for ((node, path), unstable) in paths.iter().zip(unstable.iter()) {
+            add_path(&mut reference, &path_id(path), *node);
+
+            // A node unstable on a level moves out of all deeper modules too
+            for depth in 1..=path.len() {
+                let significant = truth
+                    .entry(path_id(&path[..depth]))
+                    .or_insert_with(HashSet::new);
+
+                if !unstable[..depth].contains(&true) {
+                    significant.insert(*node);
+                }
+            }
+        }
+
+        networks.insert(0, reference);
+
+        for network_id in 1..=self.bootstraps {
+            let mut paths = paths.clone();
+
+            for ((_, path), unstable) in paths.iter_mut().zip(unstable.iter()) {
+                for (depth, level) in self.levels.iter().enumerate() {
+                    if unstable[depth] && rng.gen_bool(level.move_prob) {
+                        path[depth] = sibling(path[depth], level.branching, &mut rng);
+
+                        for (label, level) in
+                            path[depth + 1..].iter_mut().zip(&self.levels[depth + 1..])
+                        {
+                            *label = rng.gen_range(1..=level.branching);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            // Split or merge planted modules, top down
+            for (depth, level) in self.levels.iter().enumerate() {
+                let modules = paths
+                    .iter()
+                    .map(|(_, path)| path[..=depth].to_vec())
+                    .collect::<HashSet<_>>();
+
+                let mut modules = modules.into_iter().collect::<Vec<_>>();
+                modules.sort();
+
+                for module in modules.iter() {
+                    let in_module = |path: &[usize]| path[..=depth] == module[..];
+
+                    if rng.gen_bool(self.split_prob) {
+                        let label = level.branching + 1 + rng.gen_range(0..level.branching);
+                        for (_, path) in paths.iter_mut() {
+                            if in_module(path) && rng.gen_bool(0.5) {
+                                path[depth] = label;
+                            }
+                        }
+                    } else if level.branching > 1 && rng.gen_bool(self.merge_prob) {
+                        let label = sibling(module[depth], level.branching, &mut rng);
+                        for (_, path) in paths.iter_mut() {
+                            if in_module(path) {
+                                path[depth] = label;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let mut network = Network::new();
+
+            for (node, path) in paths.iter() {
+                if !rng.gen_bool(self.missing_prob) {
+                    add_path(&mut network, &path_id(path), *node);
+                }
+            }
+
+            networks.insert(network_id, network);
+        }
+
+        Synthetic {
+            networks,
+            labels,
+            truth,
+        }
+    }
+}
+
+/// Precision and recall of the significant nodes of all modules together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Accuracy {
+    pub precision: f64,
+    pub recall: f64,
+}
+
+impl Accuracy {
+    pub fn f1(&self) -> f64 {
+        match self.precision + self.recall {
+            sum if sum > 0.0 => 2.0 * self.precision * self.recall / sum,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Compares the significant cores of the reference modules to the ground truth.
+pub fn accuracy(
+    truth: &HashMap<String, HashSet<NodeId>>,
+    cores: &HashMap<String, HashSet<NodeId>>,
+) -> Accuracy {
+    let mut true_positives = 0;
+    let mut positives = 0;
+    let mut relevant = 0;
+
+    for (module_id, significant) in truth.iter() {
+        let core = cores.get(module_id);
+
+        true_positives += core.map_or(0, |core| core.intersection(significant).count());
+        positives += core.map_or(0, |core| core.len());
+        relevant += significant.len();
+    }
+
+    let ratio = |a: usize, b: usize| if b == 0 { 1.0 } else { a as f64 / b as f64 };
+
+    Accuracy {
+        precision: ratio(true_positives, positives),
+        recall: ratio(true_positives, relevant),
+    }
+}
+
+/// Fraction of reference modules matched to the bootstrap module with the same planted id.
+pub fn matching_accuracy(
+    most_similar_modules: &HashMap<String, BTreeMap<NetworkId, String>>,
+) -> f64 {
+    let (correct, total) = most_similar_modules
+        .iter()
+        .flat_map(|(module_id, matches)| matches.values().map(move |other| other == module_id))
+        .fold((0, 0), |(correct, total), matched| {
+            (correct + matched as usize, total + 1)
+        });
+
+    if total == 0 {
+        1.0
+    } else {
+        correct as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Params;
+    use crate::similarity::get_most_similar_modules;
+    use crate::Optimizer;
+
+    #[test]
+    fn test_generate() {
+        let benchmark = Benchmark {
+            missing_prob: 0.05,
+            bootstraps: 10,
+            ..Default::default()
+        };
+        let synthetic = benchmark.generate();
+
+        assert_eq!(synthetic.networks.len(), 11);
+        assert_eq!(synthetic.labels.len(), 240);
+
+        let reference = &synthetic.networks[&0];
+        assert_eq!(reference.modules.len(), 4 + 12);
+        assert_eq!(reference.modules["2:3"].nodes.len(), 20);
+        assert_eq!(synthetic.truth.len(), reference.modules.len());
+
+        for (module_id, significant) in synthetic.truth.iter() {
+            let module = &reference.modules[module_id];
+            assert!(significant.is_subset(&module.nodes));
+            assert!(significant.len() < module.nodes.len());
+        }
+
+        // The submodules are less stable than their parents
+        let significant = |level| {
+            synthetic
+                .truth
+                .iter()
+                .filter(|(module_id, _)| reference.modules[*module_id].level == level)
+                .map(|(_, nodes)| nodes.len())
+                .sum::<usize>()
+        };
+        assert!(significant(2) < significant(1));
+
+        let bootstrap = &synthetic.networks[&1];
+        assert!(bootstrap.nodes.len() < reference.nodes.len());
+
+        let again = benchmark.generate();
+        assert_eq!(again.networks[&3].nodes, synthetic.networks[&3].nodes);
+    }
+
+    #[test]
+    fn test_benchmark_optimizers() {
+        let synthetic = Benchmark {
+            bootstraps: 50,
+            ..Default::default()
+        }
+        .generate();
+        let mut networks = synthetic.networks;
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = get_most_similar_modules(&first, &networks);
+
+        assert!(matching_accuracy(&most_similar_modules) > 0.95);
+
+        for optimizer in [
+            Optimizer::Annealing,
+            Optimizer::Greedy,
+            Optimizer::GreedyAnnealing,
+        ] {
+            let cores = most_similar_modules
+                .iter()
+                .map(|(module_id, matches)| {
+                    let modules = matches
+                        .iter()
+                        .map(|(network_id, other)| &networks[network_id].modules[other].nodes)
+                        .collect::<Vec<_>>();
+                    let module = &first.modules[module_id].nodes;
+                    let result =
+                        optimizer
+                            .build(1)
+                            .optimize(module, &modules, &Params::new(0.95, 123));
+                    (module_id.clone(), result.core)
+                })
+                .collect();
+
+            let accuracy = accuracy(&synthetic.truth, &cores);
+            assert!(accuracy.precision > 0.95, "{:?}: {:?}", optimizer, accuracy);
+            assert!(accuracy.recall > 0.9, "{:?}: {:?}", optimizer, accuracy);
+        }
+    }
+}
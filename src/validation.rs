@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::io::Labels;
+use crate::similarity::jaccard_distance;
+use crate::{HashMap, HashSet, Network, NetworkId, NodeId};
+
+/// When a partition is different enough to be reported.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Largest ratio between the number of modules in a bootstrap partition
+    /// and in the reference partition, either way.
+    pub module_ratio: f32,
+    /// Smallest mean Jaccard index between the reference modules and their
+    /// best matches in a bootstrap partition.
+    pub min_jaccard: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            module_ratio: 2.0,
+            min_jaccard: 0.2,
+        }
+    }
+}
+
+/// A likely problem with one of the partitions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// Nodes in other partitions that are absent from this one.
+    AbsentNodes {
+        network_id: NetworkId,
+        absent: usize,
+        total: usize,
+    },
+    /// Nodes on more than one row of the input, or in more than one module
+    /// on the same level.
+    DuplicateNodes {
+        network_id: NetworkId,
+        nodes: usize,
+        /// Label of the first such node.
+        example: String,
+    },
+    /// Number of modules compared to the median bootstrap partition.
+    ModuleCount {
+        network_id: NetworkId,
+        modules: usize,
+        median: usize,
+    },
+    /// Number of levels compared to most bootstrap partitions.
+    Depth {
+        network_id: NetworkId,
+        depth: u8,
+        common: u8,
+    },
+    /// The reference modules barely match any module in this partition.
+    LowSimilarity { network_id: NetworkId, jaccard: f32 },
+}
+
+fn partition_name(network_id: NetworkId) -> String {
+    match network_id {
+        0 => "Reference partition".to_owned(),
+        _ => format!("Partition {}", network_id),
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Issue::AbsentNodes {
+                network_id,
+                absent,
+                total,
+            } => write!(
+                f,
+                "{}: {} of {} nodes are absent",
+                partition_name(network_id),
+                absent,
+                total
+            ),
+            Issue::DuplicateNodes {
+                network_id,
+                nodes,
+                ref example,
+            } => write!(
+                f,
+                "{}: {} nodes, such as {}, are duplicated or in more than one module on a level",
+                partition_name(network_id),
+                nodes,
+                example
+            ),
+            Issue::ModuleCount {
+                network_id,
+                modules,
+                median,
+            } => write!(
+                f,
+                "{}: {} modules, the median bootstrap partition has {}",
+                partition_name(network_id),
+                modules,
+                median
+            ),
+            Issue::Depth {
+                network_id,
+                depth,
+                common,
+            } => write!(
+                f,
+                "{}: {} levels, most bootstrap partitions have {}",
+                partition_name(network_id),
+                depth,
+                common
+            ),
+            Issue::LowSimilarity {
+                network_id,
+                jaccard,
+            } => write!(
+                f,
+                "{}: mean Jaccard index to the reference modules is only {:.3}",
+                partition_name(network_id),
+                jaccard
+            ),
+        }
+    }
+}
+
+fn depth(network: &Network) -> u8 {
+    network
+        .modules
+        .values()
+        .map(|module| module.level)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Nodes read more than once or in more than one module on the same level, sorted.
+fn duplicate_nodes(network: &Network) -> Vec<NodeId> {
+    let mut seen = HashSet::new();
+    let mut duplicates = network.duplicates.clone();
+
+    for module in network.modules.values() {
+        for &node in module.nodes.iter() {
+            if !seen.insert((module.level, node)) {
+                duplicates.insert(node);
+            }
+        }
+    }
+
+    let mut duplicates = duplicates.into_iter().collect::<Vec<_>>();
+    duplicates.sort_unstable();
+    duplicates
+}
+
+/// Checks the reference partition `first` and the bootstrap partitions `rest`
+/// for problems that silently degrade the cores, such as a corrupt bootstrap file.
+///
+/// Runs before the modules are matched, see [`validate_matches`] for the
+/// checks that need the matching.
+pub fn validate(
+    first: &Network,
+    rest: &BTreeMap<NetworkId, Network>,
+    labels: &Labels,
+    thresholds: &Thresholds,
+) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let networks =
+        std::iter::once((0, first)).chain(rest.iter().map(|(&id, network)| (id, network)));
+
+    let all_nodes = networks
+        .clone()
+        .flat_map(|(_, network)| network.nodes.iter().copied())
+        .collect::<HashSet<_>>();
+
+    // The reference partition is often deeper than the bootstrap partitions,
+    // so these are compared to the other bootstrap partitions
+    let median_modules = rest
+        .values()
+        .map(|network| network.modules.len())
+        .sorted()
+        .nth(rest.len() / 2)
+        .unwrap_or(0);
+
+    let common_depth = rest
+        .values()
+        .map(depth)
+        .counts()
+        .into_iter()
+        .max_by_key(|&(depth, count)| (count, std::cmp::Reverse(depth)))
+        .map_or(0, |(depth, _)| depth);
+
+    for (network_id, network) in networks {
+        let absent = all_nodes.len() - network.nodes.len();
+
+        if absent > 0 {
+            issues.push(Issue::AbsentNodes {
+                network_id,
+                absent,
+                total: all_nodes.len(),
+            });
+        }
+
+        let duplicates = duplicate_nodes(network);
+
+        if let Some(&example) = duplicates.first() {
+            issues.push(Issue::DuplicateNodes {
+                network_id,
+                nodes: duplicates.len(),
+                example: labels.label(example).to_owned(),
+            });
+        }
+
+        if network_id == 0 {
+            continue;
+        }
+
+        let modules = network.modules.len();
+        let ratio = modules.max(1) as f32 / median_modules.max(1) as f32;
+
+        if ratio > thresholds.module_ratio || ratio < 1.0 / thresholds.module_ratio {
+            issues.push(Issue::ModuleCount {
+                network_id,
+                modules,
+                median: median_modules,
+            });
+        }
+
+        if depth(network) != common_depth {
+            issues.push(Issue::Depth {
+                network_id,
+                depth: depth(network),
+                common: common_depth,
+            });
+        }
+    }
+
+    issues
+}
+
+/// Checks how well the reference modules match the modules of each bootstrap
+/// partition in `rest`.
+pub fn validate_matches(
+    first: &Network,
+    rest: &BTreeMap<NetworkId, Network>,
+    most_similar_modules: &HashMap<String, BTreeMap<NetworkId, String>>,
+    thresholds: &Thresholds,
+) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for (&network_id, network) in rest.iter() {
+        let jaccards = most_similar_modules
+            .iter()
+            .filter_map(|(module_id, matches)| {
+                let module = &network.modules[matches.get(&network_id)?];
+                Some(1.0 - jaccard_distance(&first.modules[module_id].nodes, &module.nodes))
+            })
+            .collect::<Vec<_>>();
+
+        if !jaccards.is_empty() {
+            let jaccard = jaccards.iter().sum::<f32>() / jaccards.len() as f32;
+
+            if jaccard < thresholds.min_jaccard {
+                issues.push(Issue::LowSimilarity {
+                    network_id,
+                    jaccard,
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::get_most_similar_modules;
+
+    #[test]
+    fn test_validate() {
+        let input = "1 1:1 1 1 1:1 1
+                     2 1:1 1 1 1:2 1
+                     3 1:2 1 1 2:1 -
+                     4 2:1 2 2 2:2 2
+                     5 2:2 2 2 3:1 2
+                     6 2:2 2 2 4:1 2";
+        let (mut networks, labels) = crate::io::read_input(input).unwrap();
+        let first = networks.remove(&0).unwrap();
+        let issues = validate(&first, &networks, &labels, &Thresholds::default());
+
+        assert_eq!(
+            issues,
+            vec![
+                Issue::ModuleCount {
+                    network_id: 3,
+                    modules: 10,
+                    median: 2
+                },
+                Issue::Depth {
+                    network_id: 3,
+                    depth: 2,
+                    common: 1
+                },
+                Issue::AbsentNodes {
+                    network_id: 4,
+                    absent: 1,
+                    total: 6
+                },
+            ]
+        );
+
+        let mut network = networks[&1].clone();
+        network.add_node("1", 3);
+        let rest = BTreeMap::from([(1, network)]);
+        let issues = validate(&first, &rest, &labels, &Thresholds::default());
+        assert_eq!(
+            issues,
+            vec![Issue::DuplicateNodes {
+                network_id: 1,
+                nodes: 1,
+                example: "4".to_owned()
+            }]
+        );
+
+        // Duplicate rows are merged into one node, but still reported
+        let (mut networks, labels) = crate::io::read_input("a 1 1\nb 1 2\na 1 -\n").unwrap();
+        let first = networks.remove(&0).unwrap();
+        let issues = validate(&first, &networks, &labels, &Thresholds::default());
+        assert_eq!(
+            issues,
+            vec![Issue::DuplicateNodes {
+                network_id: 0,
+                nodes: 1,
+                example: "a".to_owned()
+            }]
+        );
+        assert!(issues[0].to_string().contains("such as a,"));
+    }
+
+    #[test]
+    fn test_validate_matches() {
+        let input = "1 1:1 1 1 1:1 1
+                     2 1:1 1 1 1:2 1
+                     3 1:2 1 1 2:1 -
+                     4 2:1 2 2 2:2 2
+                     5 2:2 2 2 3:1 2
+                     6 2:2 2 2 4:1 2";
+        let (mut networks, _) = crate::io::read_input(input).unwrap();
+        let first = networks.remove(&0).unwrap();
+        let most_similar_modules = get_most_similar_modules(&first, &networks);
+
+        let issues = validate_matches(
+            &first,
+            &networks,
+            &most_similar_modules,
+            &Thresholds::default(),
+        );
+        assert!(issues.is_empty());
+
+        let thresholds = Thresholds {
+            min_jaccard: 0.9,
+            ..Default::default()
+        };
+        let issues = validate_matches(&first, &networks, &most_similar_modules, &thresholds);
+        assert!(issues
+            .iter()
+            .any(|issue| matches!(issue, Issue::LowSimilarity { network_id: 1, .. })));
+    }
+}